@@ -4,9 +4,12 @@
 //! using the json-rpc-rs library with HTTP transport. When the user presses CTRL+C,
 //! the server will:
 //!
-//! 1. Stop accepting new connections
-//! 2. Wait for in-flight requests to complete
-//! 3. Exit cleanly
+//! 1. Report not ready on `/readyz` so load balancers stop sending traffic
+//! 2. Keep serving for a grace period, so the readiness probe sees the change
+//!    before the listener closes
+//! 3. Stop accepting new connections
+//! 4. Wait for in-flight requests to complete
+//! 5. Exit cleanly
 //!
 //! The example includes a `long_running_operation` method that simulates work,
 //! allowing you to see how pending requests are handled during shutdown.
@@ -30,20 +33,32 @@
 //!
 //! Expected behavior:
 //! - The server will log that shutdown was initiated
+//! - `/readyz` returns 503 while `/livez` still returns 200 for the grace period
 //! - The long-running request will complete
 //! - The server will exit gracefully after all pending requests finish
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Result;
-use axum::{Router, routing::post};
+use axum::{
+    Router,
+    routing::{get, post},
+};
 use json_rpc::JsonRpc;
-use json_rpc::axum::handler;
+use json_rpc::axum::{handler, liveness, readiness};
+use json_rpc::health::ReadinessState;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{error, info};
 
+/// How long the server keeps serving after it reports draining.
+///
+/// This should be longer than the readiness probe period of the orchestrator,
+/// so at least one probe sees 503 before the listener closes.
+const DRAIN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 /// Parameters for the long running operation.
 #[derive(Debug, Deserialize)]
 struct LongRunningParams {
@@ -129,9 +144,17 @@ async fn main() -> Result<()> {
         .add("echo", echo)
         .add("health", health);
 
+    let readiness_state = ReadinessState::new();
+
+    let health = Router::new()
+        .route("/livez", get(liveness))
+        .route("/readyz", get(readiness))
+        .with_state(readiness_state.clone());
+
     let app = Router::new()
         .route("/jsonrpc", post(handler))
-        .with_state(Arc::new(json_rpc));
+        .with_state(Arc::new(json_rpc))
+        .merge(health);
 
     let addr: std::net::SocketAddr = "127.0.0.1:3000".parse()?;
     let listener = tokio::net::TcpListener::bind(addr).await?;
@@ -139,6 +162,8 @@ async fn main() -> Result<()> {
 
     info!("Server started on http://{}", local_addr);
     info!("JSON-RPC endpoint: http://{}/jsonrpc", local_addr);
+    info!("Liveness probe: http://{}/livez", local_addr);
+    info!("Readiness probe: http://{}/readyz", local_addr);
     info!("Available methods:");
     info!("  - long_running_operation: Simulates long work (params: {{duration_ms: number}})");
     info!("  - echo: Echoes back any JSON params");
@@ -147,7 +172,8 @@ async fn main() -> Result<()> {
     info!("Press CTRL+C to initiate graceful shutdown");
     info!("The server will wait for in-flight requests to complete before exiting");
 
-    let shutdown_signal = async {
+    let draining_state = readiness_state.clone();
+    let shutdown_signal = async move {
         match tokio::signal::ctrl_c().await {
             Ok(()) => {
                 info!("CTRL+C received - initiating graceful shutdown");
@@ -156,10 +182,17 @@ async fn main() -> Result<()> {
                 error!("Failed to listen for shutdown signal: {}", err);
             }
         }
-        info!("Shutdown signal received");
+        draining_state.set_draining();
+        info!(
+            "Reporting not ready for {:?} before closing the listener",
+            DRAIN_GRACE_PERIOD
+        );
+        tokio::time::sleep(DRAIN_GRACE_PERIOD).await;
+        info!("Grace period over - closing the listener");
     };
 
     info!("Starting server with graceful shutdown support");
+    readiness_state.set_ready();
 
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal)
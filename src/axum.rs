@@ -6,6 +6,11 @@
 //! The handler reads the HTTP request body, calls `JsonRpc::call()`, and returns
 //! the HTTP response. This follows the Bring Your Own Transport pattern: axum
 //! handles the HTTP transport, the library handles JSON-RPC message processing.
+//! `strict_handler` works the same way but only accepts `application/json`
+//! request bodies, and `get_handler` takes the message from a query parameter
+//! of a GET request.
+//! The `liveness` and `readiness` handlers expose health probes for
+//! orchestrators such as Kubernetes.
 //!
 //! ```toml
 //! [dependencies]
@@ -16,7 +21,7 @@
 //!
 //! ```no_run
 //! use json_rpc::{JsonRpc, axum::handler};
-//! use axum::Router;
+//! use axum::{Router, routing::post};
 //! use std::sync::Arc;
 //!
 //! async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
@@ -24,37 +29,46 @@
 //! }
 //!
 //! let json_rpc = JsonRpc::new().add("echo", echo);
-//! let app = Router::new()
-//!     .route("/jsonrpc", handler)
+//! let app: Router = Router::new()
+//!     .route("/jsonrpc", post(handler))
 //!     .with_state(Arc::new(json_rpc));
 //! ```
 
 use std::sync::Arc;
 
 use axum::{
-    extract::{Request, State},
-    http::{StatusCode, header},
+    extract::{Query, Request, State},
+    http::{HeaderName, HeaderValue, StatusCode, header},
     response::IntoResponse,
 };
 
 use crate::JsonRpc;
+use crate::health::ReadinessState;
+use crate::jsonrpc::ResponseMetadata;
+use crate::types::{RequestId, Response};
 
 /// Axum handler for processing JSON-RPC requests.
 ///
 /// This handler extracts the HTTP request body, calls `JsonRpc::call()` with the
 /// JSON string, and returns the HTTP response. Returns HTTP 204 No Content for
-/// notifications (JSON-RPC requests without an `id` field).
+/// notifications (JSON-RPC requests without an `id` field). Metadata from a
+/// hook registered with `JsonRpc::on_response` is sent as response headers;
+/// pairs that are not valid HTTP headers are skipped.
 ///
 /// The handler limits request body size to 10MB to prevent memory exhaustion.
 ///
 /// ```no_run
 /// use json_rpc::{JsonRpc, axum::handler};
-/// use axum::Router;
+/// use axum::{Router, routing::post};
 /// use std::sync::Arc;
-//!
-//! let json_rpc = JsonRpc::new().add("echo", echo);
-/// let app = Router::new()
-//!     .route("/jsonrpc", handler)
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let app: Router = Router::new()
+///     .route("/jsonrpc", post(handler))
 ///     .with_state(Arc::new(json_rpc));
 /// ```
 pub async fn handler(State(json_rpc): State<Arc<JsonRpc>>, request: Request) -> impl IntoResponse {
@@ -62,10 +76,7 @@ pub async fn handler(State(json_rpc): State<Arc<JsonRpc>>, request: Request) ->
         Ok(b) => b,
         Err(e) => {
             tracing::error!("Failed to read request body: {}", e);
-            return error_response(
-                StatusCode::BAD_REQUEST,
-                r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#,
-            );
+            return parse_error_response(&json_rpc);
         }
     };
 
@@ -73,19 +84,18 @@ pub async fn handler(State(json_rpc): State<Arc<JsonRpc>>, request: Request) ->
         Ok(s) => s,
         Err(_) => {
             tracing::error!("Invalid UTF-8 in request body");
-            return error_response(
-                StatusCode::BAD_REQUEST,
-                r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#,
-            );
+            return parse_error_response(&json_rpc);
         }
     };
 
-    tracing::debug!("Processing JSON-RPC request: {}", json_str);
+    tracing::debug!("Processing JSON-RPC request of {} bytes", json_str.len());
 
-    match json_rpc.call(&json_str).await {
-        Some(response_json) => {
-            tracing::debug!("Sending JSON-RPC response: {}", response_json);
-            success_response(&response_json)
+    match json_rpc.call_with_metadata(&json_str).await {
+        Some((response_json, metadata)) => {
+            tracing::debug!("Sending JSON-RPC response of {} bytes", response_json.len());
+            let mut response = success_response(&response_json);
+            append_headers(&mut response, metadata);
+            response
         }
         None => {
             tracing::debug!("Notification processed - no response needed");
@@ -94,6 +104,154 @@ pub async fn handler(State(json_rpc): State<Arc<JsonRpc>>, request: Request) ->
     }
 }
 
+/// Axum handler that only accepts JSON request bodies.
+///
+/// This works like [`handler`], except that a request whose `Content-Type`
+/// is not `application/json` gets HTTP 415 Unsupported Media Type with an
+/// Invalid Request error body, and is not processed. Parameters such as
+/// `charset=utf-8` are allowed. Use it when CORS rules or content
+/// negotiation rely on the content type; [`handler`] accepts any.
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, axum::strict_handler};
+/// use axum::{Router, routing::post};
+/// use std::sync::Arc;
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let app: Router = Router::new()
+///     .route("/jsonrpc", post(strict_handler))
+///     .with_state(Arc::new(json_rpc));
+/// ```
+pub async fn strict_handler(
+    State(json_rpc): State<Arc<JsonRpc>>,
+    request: Request,
+) -> axum::response::Response {
+    if !is_json(&request) {
+        tracing::debug!("Rejecting request body that is not application/json");
+        let response =
+            Response::error(RequestId::Null, json_rpc.error_messages().invalid_request());
+        let json = serde_json::to_string(&response).unwrap_or_else(|e| {
+            tracing::error!("Failed to serialize invalid request response: {}", e);
+            String::new()
+        });
+        return error_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, &json);
+    }
+    handler(State(json_rpc), request).await.into_response()
+}
+
+/// Return `true` if the request declares an `application/json` body.
+fn is_json(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// The query string accepted by [`get_handler`].
+#[derive(Debug, serde::Deserialize)]
+pub struct GetRequest {
+    /// The JSON-RPC message, URL-encoded.
+    pub request: Option<String>,
+}
+
+/// Axum handler for JSON-RPC messages sent with GET.
+///
+/// The message is read from the `request` query parameter, as in
+/// `GET /jsonrpc?request=%7B%22jsonrpc%22...`, and the response is the same
+/// as from [`handler`]. A request without the parameter gets HTTP 400 with
+/// an Invalid Request error body. This is handy for trying methods from a
+/// browser. GET requests should not change state, so route it only where
+/// every reachable method is read-only; nothing is served over GET unless
+/// this handler is routed.
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, axum::{get_handler, handler}};
+/// use axum::{Router, routing::post};
+/// use std::sync::Arc;
+///
+/// async fn status(_params: ()) -> Result<&'static str, json_rpc::Error> {
+///     Ok("ok")
+/// }
+///
+/// let json_rpc = JsonRpc::new().add("status", status);
+/// let app: Router = Router::new()
+///     .route("/jsonrpc", post(handler).get(get_handler))
+///     .with_state(Arc::new(json_rpc));
+/// ```
+pub async fn get_handler(
+    State(json_rpc): State<Arc<JsonRpc>>,
+    Query(query): Query<GetRequest>,
+) -> axum::response::Response {
+    let Some(message) = query.request else {
+        tracing::debug!("Rejecting GET request without a request parameter");
+        let response =
+            Response::error(RequestId::Null, json_rpc.error_messages().invalid_request());
+        let json = serde_json::to_string(&response).unwrap_or_else(|e| {
+            tracing::error!("Failed to serialize invalid request response: {}", e);
+            String::new()
+        });
+        return error_response(StatusCode::BAD_REQUEST, &json);
+    };
+
+    match json_rpc.call_with_metadata(&message).await {
+        Some((response_json, metadata)) => {
+            let mut response = success_response(&response_json);
+            append_headers(&mut response, metadata);
+            response
+        }
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Axum handler for a liveness probe.
+///
+/// Always returns HTTP 200 OK while the process is running. Orchestrators use
+/// this to decide whether to restart the process, so it does not depend on
+/// readiness.
+///
+/// ```no_run
+/// use axum::{Router, routing::get};
+/// use json_rpc::axum::liveness;
+///
+/// let app: Router = Router::new().route("/livez", get(liveness));
+/// ```
+pub async fn liveness() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+/// Axum handler for a readiness probe.
+///
+/// Returns HTTP 200 OK when the [`ReadinessState`] is ready, and HTTP 503
+/// Service Unavailable while the server is starting or draining. Orchestrators
+/// stop routing traffic to the server while this returns 503.
+///
+/// ```no_run
+/// use axum::{Router, routing::get};
+/// use json_rpc::axum::readiness;
+/// use json_rpc::health::ReadinessState;
+///
+/// let state = ReadinessState::new();
+/// let app: Router = Router::new()
+///     .route("/readyz", get(readiness))
+///     .with_state(state.clone());
+///
+/// state.set_ready();
+/// ```
+pub async fn readiness(State(state): State<ReadinessState>) -> impl IntoResponse {
+    if state.is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        tracing::debug!("Readiness probe failed: {:?}", state.get());
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
 /// Create a successful JSON-RPC response.
 fn success_response(json: &str) -> axum::response::Response {
     (
@@ -104,6 +262,31 @@ fn success_response(json: &str) -> axum::response::Response {
         .into_response()
 }
 
+/// Add response metadata as HTTP headers.
+fn append_headers(response: &mut axum::response::Response, metadata: ResponseMetadata) {
+    for (name, value) in metadata {
+        match (
+            HeaderName::try_from(name.as_str()),
+            HeaderValue::try_from(value.as_str()),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response.headers_mut().append(name, value);
+            }
+            _ => tracing::warn!("Skipping invalid response header: {}", name),
+        }
+    }
+}
+
+/// Create a parse error response for a request body that cannot be read.
+fn parse_error_response(json_rpc: &JsonRpc) -> axum::response::Response {
+    let response = Response::error(RequestId::Null, json_rpc.error_messages().parse_error());
+    let json = serde_json::to_string(&response).unwrap_or_else(|e| {
+        tracing::error!("Failed to serialize parse error response: {}", e);
+        String::new()
+    });
+    error_response(StatusCode::BAD_REQUEST, &json)
+}
+
 /// Create an error JSON-RPC response.
 fn error_response(status: StatusCode, json: &str) -> axum::response::Response {
     (
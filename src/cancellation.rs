@@ -0,0 +1,77 @@
+//! Cooperative cancellation for in-flight requests.
+//!
+//! This module provides `CancellationToken`, a shared flag a handler checks to
+//! learn that its request was cancelled. Handlers registered with
+//! `JsonRpc::add_cancellable` receive a token for each request, and a
+//! `$/cancelRequest` notification naming the request id triggers it.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::sync::Notify;
+
+/// A cloneable handle that signals that a request was cancelled.
+///
+/// All clones share the same state. Cancellation is cooperative: triggering
+/// the token does not stop the handler, the handler decides when to check
+/// it and usually returns [`Error::Cancelled`](crate::Error::Cancelled).
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::cancellation::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+/// assert!(!token.is_cancelled());
+///
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    /// Create a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trigger the token, waking every task waiting in
+    /// [`CancellationToken::cancelled`].
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Return `true` once the token has been triggered.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Return `true` if `other` is a clone of this token.
+    pub(crate) fn same_as(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Wait until the token is triggered.
+    ///
+    /// Returns immediately if it already was.
+    pub async fn cancelled(&self) {
+        let notified = self.inner.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
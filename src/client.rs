@@ -0,0 +1,277 @@
+//! A JSON-RPC client over any `AsyncJsonRpcIo` transport.
+//!
+//! This module provides `Client`, the calling side of the protocol. It builds
+//! requests with increasing numeric ids, or ids from a custom generator, sends
+//! them through an `AsyncJsonRpcIo` implementation, waits for the response
+//! with the same id, and deserializes its result. Error objects from the
+//! server come back as `Error::RpcError` with their code, message and data, or
+//! as an `ErrorResponse` with typed data from `Client::call_typed_error`.
+//!
+//! A timeout set with `Client::with_default_timeout` or
+//! `Client::call_with_timeout` is sent with the request so the server stops
+//! working on it when the client stops waiting.
+//!
+//! The client sends one request at a time and expects the peer to answer it
+//! before the next call, which is how a stdio or socket connection to a single
+//! server usually behaves.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use json_rpc::{Client, io::LineIo};
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let socket = tokio::net::TcpStream::connect("127.0.0.1:9000").await.unwrap();
+//! let (reader, writer) = socket.into_split();
+//! let mut client = Client::new(LineIo::new(reader, writer));
+//!
+//! let difference: i32 = client.call("subtract", (42, 23)).await.unwrap();
+//! assert_eq!(difference, 19);
+//! # });
+//! ```
+
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::error::Error;
+use crate::io::AsyncJsonRpcIo;
+use crate::jsonrpc::TIMEOUT_FIELD;
+use crate::types::{Notification, Request, RequestId, Response};
+
+/// Type alias for functions that produce request ids.
+type IdGenerator = Box<dyn FnMut() -> RequestId + Send>;
+
+/// An error response whose `data` was deserialized into `E`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorResponse<E> {
+    /// The error code sent by the server.
+    pub code: i32,
+    /// The error message sent by the server.
+    pub message: String,
+    /// The error data, or `None` if the server sent none.
+    pub data: Option<E>,
+}
+
+/// A JSON-RPC client that sends requests through a transport.
+pub struct Client<I> {
+    io: I,
+    next_id: IdGenerator,
+    default_timeout: Option<Duration>,
+}
+
+impl<I: AsyncJsonRpcIo> Client<I> {
+    /// Create a client that talks through `io`.
+    ///
+    /// Requests get increasing numeric ids starting at 1.
+    pub fn new(io: I) -> Self {
+        let mut next = 0;
+        Self {
+            io,
+            next_id: Box::new(move || {
+                next += 1;
+                RequestId::Number(next)
+            }),
+            default_timeout: None,
+        }
+    }
+
+    /// Use `generator` to assign the id of each request.
+    ///
+    /// Use this for UUIDs, ids with a per-client prefix for debugging, or
+    /// ids from an external sequence. Ids must be unique among the requests
+    /// the client has in flight, or responses cannot be matched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::{Client, RequestId, io::LineIo};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let (reader, writer) = (tokio::io::stdin(), tokio::io::stdout());
+    /// let mut count = 0;
+    /// let client = Client::new(LineIo::new(reader, writer)).with_id_generator(move || {
+    ///     count += 1;
+    ///     RequestId::String(format!("worker-1/{}", count))
+    /// });
+    /// # });
+    /// ```
+    pub fn with_id_generator<G>(mut self, generator: G) -> Self
+    where
+        G: FnMut() -> RequestId + Send + 'static,
+    {
+        self.next_id = Box::new(generator);
+        self
+    }
+
+    /// Limit how long each call may take unless the call sets its own limit.
+    ///
+    /// The timeout is sent with each request as the
+    /// [`TIMEOUT_FIELD`](crate::jsonrpc::TIMEOUT_FIELD) member, and a
+    /// `JsonRpc` server answers with a "Request timed out" error with code
+    /// `-32001` once it passes, even if the server's own request timeout is
+    /// longer. The client also stops waiting at the same limit and returns
+    /// that error itself, for servers that ignore the member.
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Call a method and wait for its result.
+    ///
+    /// Messages that arrive before the matching response, such as responses
+    /// to other ids or notifications from the server, are skipped. Returns
+    /// `Error::RpcError` with the code, message and data of an error
+    /// response, and a protocol error if the transport closes before the
+    /// response arrives.
+    pub async fn call<P, R>(&mut self, method: &str, params: P) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let result = self
+            .call_within(method, params, self.default_timeout)
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Call a method and deserialize the `data` of an error response into `E`.
+    ///
+    /// Error responses come back as `Ok(Err(ErrorResponse))`. The outer error
+    /// is for failures that are not error responses, such as a closed
+    /// transport, a timeout, or `data` that does not deserialize into `E`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::{Client, io::LineIo};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Conflict {
+    ///     existing: String,
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let (reader, writer) = (tokio::io::stdin(), tokio::io::stdout());
+    /// let mut client = Client::new(LineIo::new(reader, writer));
+    ///
+    /// match client.call_typed_error::<_, u64, Conflict>("register", "alice").await {
+    ///     Ok(Ok(id)) => println!("registered as {}", id),
+    ///     Ok(Err(error)) => match error.data {
+    ///         Some(conflict) => println!("taken by {}", conflict.existing),
+    ///         None => println!("{}", error.message),
+    ///     },
+    ///     Err(error) => eprintln!("call failed: {}", error),
+    /// }
+    /// # });
+    /// ```
+    pub async fn call_typed_error<P, R, E>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<Result<R, ErrorResponse<E>>, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        match self.call_within(method, params, self.default_timeout).await {
+            Ok(result) => Ok(Ok(serde_json::from_value(result)?)),
+            Err(Error::RpcError {
+                code,
+                message,
+                data,
+            }) => Ok(Err(ErrorResponse {
+                code,
+                message,
+                data: data.map(serde_json::from_value).transpose()?,
+            })),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Call a method with a timeout for this call only.
+    ///
+    /// The timeout replaces the one from [`Client::with_default_timeout`].
+    pub async fn call_with_timeout<P, R>(
+        &mut self,
+        method: &str,
+        params: P,
+        timeout: Duration,
+    ) -> Result<R, Error>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let result = self.call_within(method, params, Some(timeout)).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Send a notification. The server does not answer notifications.
+    pub async fn notify<P: Serialize>(&mut self, method: &str, params: P) -> Result<(), Error> {
+        let notification = Notification::new(method, Some(serde_json::to_value(params)?));
+        self.io.send(&serde_json::to_string(&notification)?).await
+    }
+
+    /// Return the transport, consuming the client.
+    pub fn into_inner(self) -> I {
+        self.io
+    }
+
+    /// Send a request with an optional timeout and wait for its result.
+    async fn call_within<P: Serialize>(
+        &mut self,
+        method: &str,
+        params: P,
+        timeout: Option<Duration>,
+    ) -> Result<serde_json::Value, Error> {
+        let id = (self.next_id)();
+
+        let request = Request::new(id.clone(), method, Some(serde_json::to_value(params)?));
+        let mut request = serde_json::to_value(&request)?;
+        if let (Some(timeout), Some(object)) = (timeout, request.as_object_mut()) {
+            let millis = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+            object.insert(TIMEOUT_FIELD.to_string(), millis.into());
+        }
+        self.io.send(&serde_json::to_string(&request)?).await?;
+
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.wait_for(&id))
+                .await
+                .map_err(|_| Error::rpc(-32001, "Request timed out"))??,
+            None => self.wait_for(&id).await?,
+        };
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Error::RpcError {
+                code: error.code,
+                message: error.message,
+                data: error.data,
+            }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(Error::protocol("Response has neither result nor error")),
+        }
+    }
+
+    /// Receive messages until the response for `id` arrives.
+    async fn wait_for(&mut self, id: &RequestId) -> Result<Response, Error> {
+        loop {
+            let Some(message) = self.io.recv().await? else {
+                return Err(Error::protocol("Connection closed before response"));
+            };
+            let message = message.trim();
+            if message.is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Response>(message) {
+                Ok(response) if &response.id == id => return Ok(response),
+                Ok(response) => {
+                    tracing::debug!("Skipping response for unexpected id {}", response.id)
+                }
+                Err(_) => tracing::debug!("Skipping message that is not a response: {}", message),
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Matching responses to pending requests.
+//!
+//! This module provides `Correlator`, the bookkeeping any client or duplex
+//! peer needs when several requests can be in flight at once: each request
+//! gets a fresh id and a channel, and each incoming response is routed to the
+//! channel registered under its id.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use tokio::sync::oneshot;
+
+use crate::types::{RequestId, Response};
+
+/// Assigns request ids and routes responses back to their callers.
+///
+/// `Correlator` is shared by reference, so a reader task can call
+/// [`Correlator::complete`] while other tasks register and await requests.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::correlator::Correlator;
+/// use json_rpc::Response;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let correlator = Correlator::new();
+/// let (id, receiver) = correlator.register();
+///
+/// // Send a request with `id`, then feed the response from the reader:
+/// correlator.complete(Response::success(id, serde_json::json!(19)));
+///
+/// assert_eq!(receiver.await.unwrap().result, Some(serde_json::json!(19)));
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Correlator {
+    next_id: AtomicI64,
+    pending: Mutex<HashMap<RequestId, oneshot::Sender<Response>>>,
+}
+
+impl Correlator {
+    /// Create a correlator with no pending requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocate an id for a new request and a receiver for its response.
+    ///
+    /// Ids are increasing numbers starting at 1.
+    pub fn register(&self) -> (RequestId, oneshot::Receiver<Response>) {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let (sender, receiver) = oneshot::channel();
+        self.lock().insert(id.clone(), sender);
+        (id, receiver)
+    }
+
+    /// Deliver `response` to the request registered under its id.
+    ///
+    /// Returns `false` if no request with that id is pending, for example a
+    /// late response to a request that was forgotten after a timeout, or a
+    /// spurious one. Such a response is logged and dropped, and the pending
+    /// requests are left untouched.
+    pub fn complete(&self, response: Response) -> bool {
+        let Some(sender) = self.lock().remove(&response.id) else {
+            tracing::warn!("Dropping response for unknown request id {}", response.id);
+            return false;
+        };
+        if sender.send(response).is_err() {
+            tracing::debug!("Dropping response for a request nobody awaits");
+        }
+        true
+    }
+
+    /// Stop waiting for the request registered under `id`.
+    ///
+    /// Call this when giving up on a request, such as after a timeout, so
+    /// its entry does not stay pending. A response that arrives later is
+    /// treated as unmatched. Returns `false` if no request with that id is
+    /// pending.
+    pub fn forget(&self, id: &RequestId) -> bool {
+        self.lock().remove(id).is_some()
+    }
+
+    /// Return the number of requests still waiting for a response.
+    pub fn pending(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<RequestId, oneshot::Sender<Response>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -13,9 +13,13 @@ pub enum Error {
     #[error("Protocol error: {0}")]
     ProtocolError(String),
 
-    /// JSON-RPC error with specific code and message.
+    /// JSON-RPC error with specific code and message, and optional data.
     #[error("JSON-RPC error: code={code}, message={message}")]
-    RpcError { code: i32, message: String },
+    RpcError {
+        code: i32,
+        message: String,
+        data: Option<serde_json::Value>,
+    },
 
     /// JSON parsing error.
     #[error("Protocol error: {0}")]
@@ -24,6 +28,14 @@ pub enum Error {
     /// Invalid JSON-RPC request error.
     #[error("Invalid Request: {0}")]
     InvalidRequest(String),
+
+    /// Request params that do not match the handler's parameter type.
+    #[error("Invalid params: {0}")]
+    InvalidParams(serde_json::Error),
+
+    /// The request was cancelled by the client.
+    #[error("Request cancelled")]
+    Cancelled,
 }
 
 impl Error {
@@ -37,6 +49,30 @@ impl Error {
         Self::RpcError {
             code,
             message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Create a new JSON-RPC error with a `data` payload.
+    ///
+    /// The data is sent as the `data` member of the error object, so clients
+    /// get machine-readable details such as validation errors per field.
+    ///
+    /// ```
+    /// use json_rpc::Error;
+    /// use serde_json::json;
+    ///
+    /// let error = Error::rpc_with_data(
+    ///     -32000,
+    ///     "Validation failed",
+    ///     json!({"fields": {"email": "must contain @"}}),
+    /// );
+    /// ```
+    pub fn rpc_with_data(code: i32, message: impl Into<String>, data: serde_json::Value) -> Self {
+        Self::RpcError {
+            code,
+            message: message.into(),
+            data: Some(data),
         }
     }
 
@@ -44,4 +80,64 @@ impl Error {
     pub fn invalid_request(message: impl Into<String>) -> Self {
         Self::InvalidRequest(message.into())
     }
+
+    /// Create a Parse error (`-32700`) to send to the client.
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        Self::rpc(-32700, message)
+    }
+
+    /// Create a Method not found error (`-32601`) to send to the client.
+    pub fn method_not_found(message: impl Into<String>) -> Self {
+        Self::rpc(-32601, message)
+    }
+
+    /// Create an Invalid params error (`-32602`) to send to the client.
+    ///
+    /// Use this for params that deserialize but fail the handler's own
+    /// checks:
+    ///
+    /// ```
+    /// use json_rpc::Error;
+    ///
+    /// async fn withdraw(amount: i64) -> Result<i64, Error> {
+    ///     if amount <= 0 {
+    ///         return Err(Error::invalid_params("amount must be positive"));
+    ///     }
+    ///     Ok(amount)
+    /// }
+    /// ```
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::rpc(-32602, message)
+    }
+
+    /// Create an Internal error (`-32603`) to send to the client.
+    pub fn internal_error(message: impl Into<String>) -> Self {
+        Self::rpc(-32603, message)
+    }
+}
+
+/// Convert an error returned by a handler into the error object sent on the
+/// wire.
+///
+/// `RpcError` keeps its code, message and data. `Cancelled` becomes `-32800`
+/// "Request cancelled", and `InvalidParams` becomes `-32602` "Invalid params"
+/// with the serde message as data. Every other variant is sent as a `-32603`
+/// internal error with the error's display text.
+impl From<&Error> for crate::types::Error {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::RpcError {
+                code,
+                message,
+                data,
+            } => Self::new(*code, message.clone(), data.clone()),
+            Error::Cancelled => Self::new(-32800, "Request cancelled", None),
+            Error::InvalidParams(source) => Self::new(
+                -32602,
+                "Invalid params",
+                Some(serde_json::Value::String(source.to_string())),
+            ),
+            _ => Self::new(-32603, error.to_string(), None),
+        }
+    }
 }
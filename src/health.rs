@@ -0,0 +1,100 @@
+//! Readiness tracking for orchestrators.
+//!
+//! This module provides `ReadinessState`, a shared flag your application flips
+//! as it moves through its lifecycle. Orchestrators such as Kubernetes use two
+//! separate signals: liveness (the process is running) and readiness (the
+//! process should receive traffic). A server is live for its whole lifetime,
+//! but it is only ready after warmup and stops being ready while it drains
+//! during shutdown.
+//!
+//! With the `axum` feature, `axum::liveness` and `axum::readiness` expose these
+//! signals as HTTP endpoints.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The lifecycle stage reported by a [`ReadinessState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// The server is running but still warming up.
+    Starting,
+    /// The server accepts traffic.
+    Ready,
+    /// The server is shutting down and should not receive new traffic.
+    Draining,
+}
+
+impl Readiness {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Readiness::Ready,
+            2 => Readiness::Draining,
+            _ => Readiness::Starting,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Readiness::Starting => 0,
+            Readiness::Ready => 1,
+            Readiness::Draining => 2,
+        }
+    }
+}
+
+/// A cloneable handle to the readiness of a server.
+///
+/// All clones share the same state, so you can keep one clone in your startup
+/// and shutdown code and hand another to the health endpoints. A new state
+/// starts as [`Readiness::Starting`].
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::health::{Readiness, ReadinessState};
+///
+/// let state = ReadinessState::new();
+/// assert!(!state.is_ready());
+///
+/// state.set_ready();
+/// assert!(state.is_ready());
+///
+/// state.set_draining();
+/// assert_eq!(state.get(), Readiness::Draining);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReadinessState {
+    inner: Arc<AtomicU8>,
+}
+
+impl ReadinessState {
+    /// Create a new state in the [`Readiness::Starting`] stage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the current stage.
+    pub fn get(&self) -> Readiness {
+        Readiness::from_u8(self.inner.load(Ordering::Acquire))
+    }
+
+    /// Set the current stage.
+    pub fn set(&self, readiness: Readiness) {
+        self.inner.store(readiness.as_u8(), Ordering::Release);
+    }
+
+    /// Mark the server as ready to accept traffic.
+    pub fn set_ready(&self) {
+        self.set(Readiness::Ready);
+    }
+
+    /// Mark the server as draining during shutdown.
+    pub fn set_draining(&self) {
+        self.set(Readiness::Draining);
+    }
+
+    /// Return `true` if the server should receive traffic.
+    pub fn is_ready(&self) -> bool {
+        self.get() == Readiness::Ready
+    }
+}
@@ -0,0 +1,185 @@
+//! Response caching for idempotent request delivery.
+//!
+//! Transports with at-least-once delivery (such as MQTT or Redis queues) can
+//! deliver the same request twice. When idempotency is enabled with
+//! `JsonRpc::with_idempotency`, a request that carries an idempotency key has
+//! its response cached for a time-to-live. A duplicate request with the same
+//! key gets the cached response and the handler does not run again. A
+//! duplicate that arrives while the first request is still running waits for
+//! its response. The cached response carries the id of the duplicate, so a
+//! retry that reuses the key with a new id is answered with that id.
+//!
+//! Responses are cached per key, method and params. A request that reuses a
+//! key for another method or with other params runs on its own and does not
+//! get the response of a different call.
+//!
+//! The key is read from the `idempotencyKey` member of the request object:
+//!
+//! ```json
+//! {"jsonrpc":"2.0","method":"charge","params":[42],"id":1,"idempotencyKey":"order-7"}
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use crate::types::{Request, Response};
+
+/// Name of the request member that carries the idempotency key.
+pub const IDEMPOTENCY_KEY_FIELD: &str = "idempotencyKey";
+
+/// What a cached response is stored under: the idempotency key together with
+/// the method and params of the request that sent it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    key: String,
+    method: String,
+    params: Option<serde_json::Value>,
+}
+
+impl CacheKey {
+    /// Build the cache key of `request`, sent with idempotency key `key`.
+    pub(crate) fn new(key: String, request: &Request) -> Self {
+        Self {
+            key,
+            method: request.method.clone(),
+            params: request.params.clone(),
+        }
+    }
+
+    /// Return the idempotency key the client sent.
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// A cache entry, either still being computed or stored at an instant.
+enum Entry {
+    Running(watch::Receiver<Option<Response>>),
+    Done(Instant, Response),
+}
+
+/// The state of a key when a request with it arrives.
+pub(crate) enum Lookup<'a> {
+    /// A response was cached for the key.
+    Cached(Response),
+    /// Another request with the key is running; wait for its response.
+    Running(Running),
+    /// No request with the key ran yet; this one computes the response.
+    First(Claim<'a>),
+}
+
+/// A wait for the response of a request that is still running.
+pub(crate) struct Running(watch::Receiver<Option<Response>>);
+
+impl Running {
+    /// Wait for the response.
+    ///
+    /// Returns `None` if the running request finished without storing one,
+    /// such as when it timed out.
+    pub(crate) async fn wait(mut self) -> Option<Response> {
+        let response = self.0.wait_for(Option::is_some).await.ok()?;
+        response.clone()
+    }
+}
+
+/// The right to compute the response for a key.
+///
+/// Dropping the claim without [`Claim::finish`] removes the running entry, so
+/// the next request with the key runs again.
+pub(crate) struct Claim<'a> {
+    cache: &'a IdempotencyCache,
+    key: CacheKey,
+    sender: watch::Sender<Option<Response>>,
+    finished: bool,
+}
+
+impl Claim<'_> {
+    /// Store `response` for the key and hand it to the waiting requests.
+    pub(crate) fn finish(mut self, response: Response) {
+        let mut entries = self.cache.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(
+            self.key.clone(),
+            Entry::Done(Instant::now(), response.clone()),
+        );
+        self.sender.send_replace(Some(response));
+        self.finished = true;
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let mut entries = self.cache.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(entries.get(&self.key), Some(Entry::Running(_))) {
+            entries.remove(&self.key);
+        }
+    }
+}
+
+/// A cache of responses keyed by idempotency key, with a fixed time-to-live.
+///
+/// A key is recorded as running as soon as the first request with it starts,
+/// so a duplicate that arrives before the response is ready waits for it
+/// instead of running the handler a second time.
+pub(crate) struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+}
+
+impl IdempotencyCache {
+    /// Create an empty cache whose entries expire after `ttl`.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Look up `key`, claiming it if no request with it has run yet.
+    ///
+    /// Expired entries are dropped first.
+    pub(crate) fn lookup(&self, key: &CacheKey) -> Lookup<'_> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| match entry {
+            Entry::Running(_) => true,
+            Entry::Done(stored_at, _) => stored_at.elapsed() < self.ttl,
+        });
+        match entries.get(key) {
+            Some(Entry::Done(_, response)) => Lookup::Cached(response.clone()),
+            Some(Entry::Running(receiver)) => Lookup::Running(Running(receiver.clone())),
+            None => {
+                let (sender, receiver) = watch::channel(None);
+                entries.insert(key.clone(), Entry::Running(receiver));
+                Lookup::First(Claim {
+                    cache: self,
+                    key: key.clone(),
+                    sender,
+                    finished: false,
+                })
+            }
+        }
+    }
+}
+
+/// Read the idempotency keys from a parsed message.
+///
+/// Returns one entry per message. A batch yields one entry per element, in the
+/// same order as the batch, so keys line up with the parsed batch messages.
+pub(crate) fn keys(value: &serde_json::Value) -> Vec<Option<String>> {
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(key).collect(),
+        other => vec![key(other)],
+    }
+}
+
+fn key(value: &serde_json::Value) -> Option<String> {
+    value
+        .get(IDEMPOTENCY_KEY_FIELD)
+        .and_then(|key| key.as_str())
+        .map(str::to_string)
+}
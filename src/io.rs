@@ -0,0 +1,842 @@
+//! A minimal interface for custom transports.
+//!
+//! This module provides the `AsyncJsonRpcIo` trait and the `serve_io` loop.
+//! Implement the trait for anything that can receive and send whole JSON-RPC
+//! messages, such as a framed duplex byte stream or a message queue, and
+//! `serve_io` takes care of reading messages, calling `JsonRpc::call()`, and
+//! sending the responses back.
+//!
+//! For byte streams that carry newline-delimited JSON, `LineIo` already
+//! implements the trait, and `serve_duplex` serves a read half and a write
+//! half directly. `ContentLengthIo` implements the `Content-Length` header
+//! framing used by the Language Server Protocol. `serve_with_shutdown` stops
+//! serving when a shutdown future completes, even while waiting for the next
+//! message, such as `ShutdownSignal::wait` from the [`shutdown`] module, and
+//! `serve_with_ready` signals a oneshot channel once serving starts.
+//! `serve_reloadable` serves whichever handler a [`reload::Registry`] holds.
+//!
+//! [`shutdown`]: crate::shutdown
+//! [`reload::Registry`]: crate::reload::Registry
+//!
+//! This is optional. You can always drive `JsonRpc::call()` from your own
+//! loop when your transport needs more control.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use json_rpc::{AsyncJsonRpcIo, Error, JsonRpc, serve_io};
+//! use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Stdin, Stdout};
+//!
+//! struct StdioLines {
+//!     reader: BufReader<Stdin>,
+//!     writer: Stdout,
+//! }
+//!
+//! impl AsyncJsonRpcIo for StdioLines {
+//!     async fn recv(&mut self) -> Result<Option<String>, Error> {
+//!         let mut line = String::new();
+//!         match self.reader.read_line(&mut line).await {
+//!             Ok(0) => Ok(None),
+//!             Ok(_) => Ok(Some(line)),
+//!             Err(e) => Err(Error::protocol(e.to_string())),
+//!         }
+//!     }
+//!
+//!     async fn send(&mut self, message: &str) -> Result<(), Error> {
+//!         let line = format!("{}\n", message);
+//!         self.writer
+//!             .write_all(line.as_bytes())
+//!             .await
+//!             .map_err(|e| Error::protocol(e.to_string()))
+//!     }
+//! }
+//!
+//! async fn echo(params: serde_json::Value) -> Result<serde_json::Value, Error> {
+//!     Ok(params)
+//! }
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let json_rpc = JsonRpc::new().add("echo", echo);
+//! let io = StdioLines {
+//!     reader: BufReader::new(tokio::io::stdin()),
+//!     writer: tokio::io::stdout(),
+//! };
+//! serve_io(io, &json_rpc).await.unwrap();
+//! # });
+//! ```
+
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::JsonRpc;
+use crate::error::Error;
+use crate::jsonrpc::{CONNECTION, Connection};
+use crate::reload::Registry;
+use crate::sink::NotificationSink;
+use crate::types::{Notification, RequestId, Response};
+
+/// A transport that receives and sends whole JSON-RPC messages.
+///
+/// Implementations handle framing: `recv` returns one complete message and
+/// `send` writes one complete message. Both can be written as `async fn` in
+/// the `impl` block.
+pub trait AsyncJsonRpcIo {
+    /// Receive the next message.
+    ///
+    /// Returns `Ok(None)` when the peer closed the transport. Return
+    /// `Error::InvalidRequest` to reject a single message, such as one over a
+    /// size limit, while keeping the transport open: the serve loop answers
+    /// it with an Invalid Request error and keeps receiving.
+    ///
+    /// With [`JsonRpc::with_concurrent_requests`] above 1 the serve loop drops
+    /// a pending `recv` whenever it has a response to send, and calls it
+    /// again afterwards. Such transports must keep what they have read of an
+    /// unfinished message instead of losing it; [`LineIo`] and
+    /// [`ContentLengthIo`] do.
+    fn recv(&mut self) -> impl Future<Output = Result<Option<String>, Error>> + Send;
+
+    /// Send one message.
+    fn send(&mut self, message: &str) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// Serving a borrowed transport leaves it with the caller, who can inspect it
+/// after [`serve_io`] returns.
+impl<I: AsyncJsonRpcIo + Send> AsyncJsonRpcIo for &mut I {
+    fn recv(&mut self) -> impl Future<Output = Result<Option<String>, Error>> + Send {
+        (**self).recv()
+    }
+
+    fn send(&mut self, message: &str) -> impl Future<Output = Result<(), Error>> + Send {
+        (**self).send(message)
+    }
+}
+
+/// Serve JSON-RPC messages from `io` until the transport closes.
+///
+/// Each received message is passed to [`JsonRpc::call_with_sink`] and its
+/// response, if any, is sent back through `io`. Notifications that handlers
+/// send while they run are written as they arrive, before the response.
+/// Messages that are empty after trimming whitespace are skipped.
+///
+/// A message that `recv` rejects with `Error::InvalidRequest` is answered
+/// with an Invalid Request error. Returns `Ok(())` when `recv` reports the
+/// end of the transport, or the first other error from `recv` or `send`.
+///
+/// Messages are handled one at a time unless
+/// [`JsonRpc::with_concurrent_requests`] allows more, so a
+/// `$/cancelRequest` notification can only reach a request that is still
+/// running when that limit is above 1. Cancellation reaches requests of the
+/// same connection only. With [`JsonRpc::stream_batch_responses`], each
+/// response of a batch is sent as soon as it is ready instead of in one
+/// array at the end.
+pub async fn serve_io<I>(io: I, json_rpc: &JsonRpc) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+{
+    serve_with_shutdown(io, json_rpc, std::future::pending()).await
+}
+
+/// Serve JSON-RPC messages from `io`, signalling `ready` once serving starts.
+///
+/// This works like [`serve_io`] and sends on `ready` right before it waits for
+/// the first message, so orchestration code can await the receiver instead of
+/// polling. Messages the peer sent earlier are not lost, they are read as soon
+/// as serving starts. A dropped receiver is ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, io::LineIo, serve_with_ready};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let (ready, started) = tokio::sync::oneshot::channel();
+/// tokio::spawn(async move {
+///     let json_rpc = JsonRpc::new().add("echo", echo);
+///     let io = LineIo::new(tokio::io::stdin(), tokio::io::stdout());
+///     serve_with_ready(io, &json_rpc, ready).await
+/// });
+///
+/// started.await.unwrap();
+/// # });
+/// ```
+pub async fn serve_with_ready<I>(
+    io: I,
+    json_rpc: &JsonRpc,
+    ready: tokio::sync::oneshot::Sender<()>,
+) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+{
+    tracing::debug!("Ready to serve");
+    let _ = ready.send(());
+    serve_io(io, json_rpc).await
+}
+
+/// Serve JSON-RPC messages from `io` until the transport closes or `shutdown`
+/// completes.
+///
+/// This works like [`serve_io`] and also returns `Ok(())` as soon as
+/// `shutdown` completes, including while it waits for the next message. A
+/// message that is already being handled is finished and its response sent
+/// first.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, io::LineIo, serve_with_shutdown};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+/// let io = LineIo::new(tokio::io::stdin(), tokio::io::stdout());
+///
+/// # drop(stop);
+/// serve_with_shutdown(io, &json_rpc, async {
+///     let _ = stopped.await;
+/// })
+/// .await
+/// .unwrap();
+/// # });
+/// ```
+pub async fn serve_with_shutdown<I, S>(io: I, json_rpc: &JsonRpc, shutdown: S) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+    S: Future<Output = ()>,
+{
+    serve_loop(io, || json_rpc, shutdown, None).await
+}
+
+/// Serve JSON-RPC messages from `io` with the handler currently installed in
+/// `registry`.
+///
+/// This works like [`serve_io`], except that each message is handled by the
+/// `JsonRpc` the registry holds when the message arrives. Installing a new
+/// handler with [`Registry::replace`] takes effect from the next message,
+/// without closing the transport. See the [`reload`] module.
+///
+/// [`reload`]: crate::reload
+pub async fn serve_reloadable<I>(io: I, registry: &Registry) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+{
+    serve_loop(io, || registry.load(), std::future::pending(), None).await
+}
+
+/// What the serve loop waits for next.
+enum Event {
+    Shutdown,
+    Received(Result<Option<String>, Error>),
+    Notification(Notification),
+    BatchResponse(String),
+    Finished(Option<String>),
+}
+
+/// Serve messages from `io` until the transport closes, `shutdown` completes,
+/// or `limit` messages have been handled.
+///
+/// Each message is handled by the `JsonRpc` that `current` returns when it
+/// arrives. Every non-empty message counts towards `limit`, including one
+/// `recv` rejected with `Error::InvalidRequest`. Up to
+/// [`JsonRpc::with_concurrent_requests`] messages run at once, and messages
+/// that are running when reading stops are finished and answered before the
+/// loop returns. The per-connection state, such as the initialize handshake of
+/// [`JsonRpc::require_initialize`] and the requests a `$/cancelRequest` can
+/// reach, lives here.
+pub(crate) async fn serve_loop<I, C, J, S>(
+    mut io: I,
+    current: C,
+    shutdown: S,
+    limit: Option<usize>,
+) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+    C: Fn() -> J,
+    J: Deref<Target = JsonRpc>,
+    S: Future<Output = ()>,
+{
+    let mut shutdown = std::pin::pin!(shutdown);
+    let (batch_responses, mut streamed) = tokio::sync::mpsc::unbounded_channel();
+    let connection = Arc::new(Connection::new(batch_responses));
+    let (sink, mut notifications) = NotificationSink::channel();
+    let mut running = FuturesUnordered::new();
+    let mut handled = 0;
+    let mut reading = true;
+    loop {
+        if reading && limit.is_some_and(|limit| handled >= limit) {
+            tracing::debug!("Handled {} messages", handled);
+            reading = false;
+        }
+        if !reading && running.is_empty() {
+            break;
+        }
+
+        let can_read = reading && running.len() < current().concurrent_requests();
+        let event = {
+            let mut recv = std::pin::pin!(io.recv());
+            std::future::poll_fn(|cx| {
+                if reading && shutdown.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Event::Shutdown);
+                }
+                if let Poll::Ready(Some(notification)) = notifications.poll_recv(cx) {
+                    return Poll::Ready(Event::Notification(notification));
+                }
+                if let Poll::Ready(Some(response)) = streamed.poll_recv(cx) {
+                    return Poll::Ready(Event::BatchResponse(response));
+                }
+                if let Poll::Ready(Some(response)) = running.poll_next_unpin(cx) {
+                    return Poll::Ready(Event::Finished(response));
+                }
+                if can_read && let Poll::Ready(received) = recv.as_mut().poll(cx) {
+                    return Poll::Ready(Event::Received(received));
+                }
+                Poll::Pending
+            })
+            .await
+        };
+
+        let message = match event {
+            Event::Shutdown => {
+                tracing::debug!("Shutdown requested");
+                reading = false;
+                continue;
+            }
+            Event::Notification(notification) => {
+                io.send(&serde_json::to_string(&notification)?).await?;
+                continue;
+            }
+            Event::BatchResponse(response) => {
+                tracing::debug!("Sending batch response of {} bytes", response.len());
+                io.send(&response).await?;
+                continue;
+            }
+            Event::Finished(response) => {
+                // Notifications a handler sent go out before its response.
+                while let Ok(notification) = notifications.try_recv() {
+                    io.send(&serde_json::to_string(&notification)?).await?;
+                }
+                match response {
+                    Some(response) => {
+                        tracing::debug!("Sending response of {} bytes", response.len());
+                        io.send(&response).await?;
+                    }
+                    None => tracing::debug!("Message processed - no response left to send"),
+                }
+                continue;
+            }
+            Event::Received(Ok(Some(message))) => message,
+            Event::Received(Ok(None)) => {
+                tracing::debug!("Transport closed");
+                reading = false;
+                continue;
+            }
+            Event::Received(Err(Error::InvalidRequest(reason))) => {
+                handled += 1;
+                tracing::warn!("Rejecting message: {}", reason);
+                let error = current().error_messages().invalid_request();
+                let response = Response::error(RequestId::Null, error);
+                io.send(&serde_json::to_string(&response)?).await?;
+                continue;
+            }
+            Event::Received(Err(e)) => return Err(e),
+        };
+
+        let message = message.trim();
+        if message.is_empty() {
+            continue;
+        }
+        handled += 1;
+
+        tracing::debug!("Processing message of {} bytes", message.len());
+        let json_rpc = current();
+        let message = message.to_string();
+        let sink = sink.clone();
+        running.push(CONNECTION.scope(Arc::clone(&connection), async move {
+            json_rpc.call_with_sink(&message, sink).await
+        }));
+    }
+
+    Ok(())
+}
+
+/// Newline-delimited JSON framing over any pair of byte stream halves.
+///
+/// Each message is one line. `recv` returns the next line and `send` writes
+/// the message followed by `\n` and flushes. Use it to serve TCP sockets,
+/// Unix sockets, pipes, or any channel your application already owns.
+///
+/// By default a line can be any length. Use [`LineIo::with_max_line_length`]
+/// to reject longer lines without buffering them.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, io::LineIo, serve_io};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let io = LineIo::new(tokio::io::stdin(), tokio::io::stdout());
+/// serve_io(io, &json_rpc).await.unwrap();
+/// # });
+/// ```
+pub struct LineIo<R, W> {
+    reader: BufReader<R>,
+    writer: W,
+    partial: PartialLine,
+    max_line_length: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<R, W> LineIo<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    /// Create a line transport reading from `reader` and writing to `writer`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            partial: PartialLine::default(),
+            max_line_length: None,
+            idle_timeout: None,
+        }
+    }
+
+    /// Reject lines longer than `limit` bytes, not counting the `\n`.
+    ///
+    /// The rest of an oversized line is read and discarded without being
+    /// kept in memory, and `recv` returns `Error::InvalidRequest`. The serve
+    /// loop answers it with an Invalid Request error and continues with the
+    /// next line.
+    pub fn with_max_line_length(mut self, limit: usize) -> Self {
+        self.max_line_length = Some(limit);
+        self
+    }
+
+    /// Close the connection when no message arrives for `timeout`.
+    ///
+    /// `recv` reports the end of the transport once it has waited `timeout`
+    /// for the next line, so the serve loop returns `Ok(())` and the
+    /// connection is dropped. The wait starts again after every message,
+    /// which keeps idle sockets from holding resources forever.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Read the next line, with the line length limit if one is set.
+    ///
+    /// The part of a line read so far is kept in `self.partial`, so dropping
+    /// this future loses nothing and the next call continues the line.
+    async fn read_line(&mut self) -> Result<Option<String>, Error> {
+        let limit = self.max_line_length.unwrap_or(usize::MAX);
+        let read_error =
+            |e: std::io::Error| Error::protocol(format!("Failed to read message: {}", e));
+        let Some((line, length)) = read_bounded_line(&mut self.reader, &mut self.partial, limit)
+            .await
+            .map_err(read_error)?
+        else {
+            return Ok(None);
+        };
+
+        if length > limit {
+            return Err(Error::invalid_request(format!(
+                "Line of {} bytes exceeds the limit of {} bytes",
+                length, limit
+            )));
+        }
+        String::from_utf8(line).map(Some).map_err(|_| {
+            Error::protocol("Failed to read message: stream did not contain valid UTF-8")
+        })
+    }
+}
+
+impl<R, W> AsyncJsonRpcIo for LineIo<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn recv(&mut self) -> Result<Option<String>, Error> {
+        let Some(timeout) = self.idle_timeout else {
+            return self.read_line().await;
+        };
+        match tokio::time::timeout(timeout, self.read_line()).await {
+            Ok(line) => line,
+            Err(_) => {
+                tracing::debug!("Closing connection idle for {:?}", timeout);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn send(&mut self, message: &str) -> Result<(), Error> {
+        let mut line = Vec::with_capacity(message.len() + 1);
+        line.extend_from_slice(message.as_bytes());
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .await
+            .map_err(|e| Error::protocol(format!("Failed to write message: {}", e)))?;
+        self.writer
+            .flush()
+            .await
+            .map_err(|e| Error::protocol(format!("Failed to write message: {}", e)))
+    }
+}
+
+/// `Content-Length` header framing, as used by the Language Server Protocol.
+///
+/// Each message is preceded by a header block of `Name: value` lines, each
+/// ending in `\r\n`, and terminated by an empty line. The `Content-Length`
+/// header gives the exact size of the message body in bytes. Other headers,
+/// such as `Content-Type`, are ignored. Outgoing messages are written with a
+/// `Content-Length` header only:
+///
+/// ```text
+/// Content-Length: 41\r\n
+/// \r\n
+/// {"jsonrpc":"2.0","result":"hello","id":1}
+/// ```
+///
+/// Bodies are limited to [`DEFAULT_MAX_MESSAGE_SIZE`] bytes unless
+/// [`ContentLengthIo::with_max_message_size`] sets another limit, so a peer
+/// cannot make the transport allocate whatever size it declares. Header
+/// lines are limited to [`MAX_HEADER_LINE_LENGTH`] bytes and a header block
+/// to [`MAX_HEADER_LINES`] lines.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, io::ContentLengthIo, serve_io};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let io = ContentLengthIo::new(tokio::io::stdin(), tokio::io::stdout());
+/// serve_io(io, &json_rpc).await.unwrap();
+/// # });
+/// ```
+pub struct ContentLengthIo<R, W> {
+    reader: BufReader<R>,
+    writer: W,
+    receiving: Receiving,
+    max_message_size: usize,
+}
+
+/// How far [`ContentLengthIo`] has read the next message.
+///
+/// Kept between calls to `recv` so a dropped `recv` loses nothing.
+enum Receiving {
+    /// Reading the header block.
+    Header {
+        line: PartialLine,
+        content_length: Option<usize>,
+        lines: usize,
+        rejected: Option<String>,
+    },
+    /// Reading a body of `length` bytes.
+    Body { body: Vec<u8>, length: usize },
+    /// Dropping the body of a rejected message.
+    Discard { remaining: usize, reason: String },
+}
+
+impl Default for Receiving {
+    fn default() -> Self {
+        Self::Header {
+            line: PartialLine::default(),
+            content_length: None,
+            lines: 0,
+            rejected: None,
+        }
+    }
+}
+
+/// The default body size limit of [`ContentLengthIo`], 64 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The longest header line [`ContentLengthIo`] accepts, 8 KiB.
+pub const MAX_HEADER_LINE_LENGTH: usize = 8 * 1024;
+
+/// The most header lines [`ContentLengthIo`] accepts in one header block.
+pub const MAX_HEADER_LINES: usize = 32;
+
+impl<R, W> ContentLengthIo<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    /// Create a framed transport reading from `reader` and writing to `writer`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            receiving: Receiving::default(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Reject message bodies longer than `limit` bytes.
+    ///
+    /// The size is checked against the `Content-Length` header before
+    /// anything is allocated. The body of an oversized message is read and
+    /// discarded without being kept in memory, and `recv` returns
+    /// `Error::InvalidRequest`. The serve loop answers it with an Invalid
+    /// Request error and continues with the next message.
+    pub fn with_max_message_size(mut self, limit: usize) -> Self {
+        self.max_message_size = limit;
+        self
+    }
+
+    /// Read the next message, continuing from `self.receiving`.
+    ///
+    /// Returns `Ok(None)` if the stream ends before a new header block starts.
+    /// A header block with a line over [`MAX_HEADER_LINE_LENGTH`] bytes or
+    /// more than [`MAX_HEADER_LINES`] lines is read to its end without being
+    /// kept, its body is discarded, and `Error::InvalidRequest` is returned.
+    /// So is a body over the size limit.
+    async fn receive(&mut self) -> Result<Option<String>, Error> {
+        let read_error =
+            |e: std::io::Error| Error::protocol(format!("Failed to read message: {}", e));
+        loop {
+            match &mut self.receiving {
+                Receiving::Header {
+                    line,
+                    content_length,
+                    lines,
+                    rejected,
+                } => {
+                    let Some((line, length)) =
+                        read_bounded_line(&mut self.reader, line, MAX_HEADER_LINE_LENGTH)
+                            .await
+                            .map_err(|e| {
+                                Error::protocol(format!("Failed to read header: {}", e))
+                            })?
+                    else {
+                        if *lines > 0 {
+                            return Err(Error::protocol("Unexpected end of stream in header"));
+                        }
+                        return Ok(None);
+                    };
+                    if length > MAX_HEADER_LINE_LENGTH {
+                        *lines += 1;
+                        rejected.get_or_insert(format!(
+                            "Header line of {} bytes exceeds the limit of {} bytes",
+                            length, MAX_HEADER_LINE_LENGTH
+                        ));
+                        continue;
+                    }
+                    let line = String::from_utf8(line)
+                        .map_err(|_| Error::protocol("Invalid UTF-8 in header"))?;
+
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if line.is_empty() {
+                        if *lines == 0 {
+                            continue;
+                        }
+                        self.receiving = match (rejected.take(), *content_length) {
+                            (Some(reason), Some(length)) => Receiving::Discard {
+                                remaining: length,
+                                reason,
+                            },
+                            (Some(reason), None) => return Err(Error::invalid_request(reason)),
+                            (None, Some(length)) if length > self.max_message_size => {
+                                Receiving::Discard {
+                                    remaining: length,
+                                    reason: format!(
+                                        "Message of {} bytes exceeds the limit of {} bytes",
+                                        length, self.max_message_size
+                                    ),
+                                }
+                            }
+                            (None, Some(length)) => Receiving::Body {
+                                body: Vec::with_capacity(length),
+                                length,
+                            },
+                            (None, None) => {
+                                return Err(Error::protocol("Missing Content-Length header"));
+                            }
+                        };
+                        continue;
+                    }
+                    *lines += 1;
+                    if *lines > MAX_HEADER_LINES {
+                        rejected.get_or_insert(format!(
+                            "Header block exceeds the limit of {} lines",
+                            MAX_HEADER_LINES
+                        ));
+                    }
+
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.trim().eq_ignore_ascii_case("content-length")
+                    {
+                        let length = value.trim().parse().map_err(|_| {
+                            Error::protocol(format!("Invalid Content-Length: {}", value.trim()))
+                        })?;
+                        *content_length = Some(length);
+                    }
+                }
+                Receiving::Body { body, length } => {
+                    let remaining = *length - body.len();
+                    if remaining == 0 {
+                        let body = std::mem::take(body);
+                        self.receiving = Receiving::default();
+                        return String::from_utf8(body)
+                            .map(Some)
+                            .map_err(|_| Error::protocol("Invalid UTF-8 in message"));
+                    }
+                    let read = (&mut self.reader)
+                        .take(remaining as u64)
+                        .read_buf(body)
+                        .await
+                        .map_err(read_error)?;
+                    if read == 0 {
+                        return Err(Error::protocol("Failed to read message: early eof"));
+                    }
+                }
+                Receiving::Discard { remaining, reason } => {
+                    if *remaining > 0 {
+                        let available = self.reader.fill_buf().await.map_err(read_error)?;
+                        if !available.is_empty() {
+                            let length = available.len().min(*remaining);
+                            self.reader.consume(length);
+                            *remaining -= length;
+                            continue;
+                        }
+                    }
+                    let reason = std::mem::take(reason);
+                    self.receiving = Receiving::default();
+                    return Err(Error::invalid_request(reason));
+                }
+            }
+        }
+    }
+}
+
+impl<R, W> AsyncJsonRpcIo for ContentLengthIo<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn recv(&mut self) -> Result<Option<String>, Error> {
+        let message = self.receive().await;
+        if message.is_err() {
+            self.receiving = Receiving::default();
+        }
+        message
+    }
+
+    async fn send(&mut self, message: &str) -> Result<(), Error> {
+        let header = format!("Content-Length: {}\r\n\r\n", message.len());
+        let mut frame = Vec::with_capacity(header.len() + message.len());
+        frame.extend_from_slice(header.as_bytes());
+        frame.extend_from_slice(message.as_bytes());
+        self.writer
+            .write_all(&frame)
+            .await
+            .map_err(|e| Error::protocol(format!("Failed to write message: {}", e)))?;
+        self.writer
+            .flush()
+            .await
+            .map_err(|e| Error::protocol(format!("Failed to write message: {}", e)))
+    }
+}
+
+/// A line that [`read_bounded_line`] has started reading.
+#[derive(Default)]
+struct PartialLine {
+    bytes: Vec<u8>,
+    length: usize,
+}
+
+/// Read the next line of `reader`, keeping at most `limit` bytes of it.
+///
+/// Returns the kept bytes, with the `\n`, and the length of the whole line
+/// without it, or `None` at the end of the stream. The rest of a longer line
+/// is consumed without being kept. What was read of an unfinished line stays
+/// in `partial`, so the future can be dropped and the read started again.
+async fn read_bounded_line<R>(
+    reader: &mut BufReader<R>,
+    partial: &mut PartialLine,
+    limit: usize,
+) -> std::io::Result<Option<(Vec<u8>, usize)>>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            if partial.length == 0 {
+                return Ok(None);
+            }
+            break;
+        }
+
+        let (chunk, complete) = match available.iter().position(|&b| b == b'\n') {
+            Some(end) => (&available[..=end], true),
+            None => (available, false),
+        };
+        let consumed = chunk.len();
+        partial.length += consumed - usize::from(complete);
+        if partial.length <= limit {
+            partial.bytes.extend_from_slice(chunk);
+        }
+        reader.consume(consumed);
+        if complete {
+            break;
+        }
+    }
+    let line = std::mem::take(partial);
+    Ok(Some((line.bytes, line.length)))
+}
+
+/// Serve newline-delimited JSON-RPC over a read half and a write half.
+///
+/// This is [`serve_io`] with [`LineIo`] framing. It returns when `reader`
+/// reaches end of file, or with the first read or write error.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, serve_duplex};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:9000").await.unwrap();
+/// let (socket, _) = listener.accept().await.unwrap();
+/// let (reader, writer) = socket.into_split();
+/// serve_duplex(reader, writer, &json_rpc).await.unwrap();
+/// # });
+/// ```
+pub async fn serve_duplex<R, W>(reader: R, writer: W, json_rpc: &JsonRpc) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    serve_io(LineIo::new(reader, writer), json_rpc).await
+}
@@ -4,25 +4,323 @@
 //! processing JSON-RPC messages. Call `JsonRpc::call()` with a JSON string to
 //! process a request and get a response string.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+use futures::future::join_all;
 use serde::Serialize;
+use serde::ser::SerializeMap;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
 
+use crate::cancellation::CancellationToken;
 use crate::error::Error;
-use crate::types::{Message, RequestId, Response};
+use crate::idempotency::{self, CacheKey, IdempotencyCache, Lookup};
+use crate::middleware::{BoxedMiddleware, Middleware, Next};
+use crate::parser::{JsonParser, SerdeJsonParser};
+use crate::result::{ResultBuilder, ResultValue};
+use crate::sink::{self, NotificationSink};
+use crate::types::{Message, Notification, Request, RequestId, Response};
 
 /// Type alias for async handler functions.
 type BoxedHandler = Box<
+    dyn Fn(serde_json::Value) -> Pin<Box<dyn Future<Output = Result<ResultValue, Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Type alias for async fallback handler functions that also receive the
+/// requested method name.
+type BoxedFallback = Box<
+    dyn Fn(
+            String,
+            serde_json::Value,
+        ) -> Pin<Box<dyn Future<Output = Result<ResultValue, Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Type alias for async handler functions that also receive the request
+/// context.
+type BoxedContextHandler = Box<
     dyn Fn(
             serde_json::Value,
-        ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value, Error>> + Send>>
+            RequestContext,
+        ) -> Pin<Box<dyn Future<Output = Result<ResultValue, Error>> + Send>>
         + Send
         + Sync,
 >;
 
+/// Type alias for async handlers that receive the whole message.
+type BoxedMessageHandler =
+    Box<dyn Fn(Message) -> Pin<Box<dyn Future<Output = Option<Response>> + Send>> + Send + Sync>;
+
+/// Cancellation tokens of running requests, keyed by request id.
+type InFlightRequests = Mutex<HashMap<RequestId, CancellationToken>>;
+
+/// Type alias for hooks that inspect serialized responses before sending.
+type BoxedResponseHook = Box<dyn Fn(&[u8]) -> ResponseMetadata + Send + Sync>;
+
+/// The notification method that cancels an in-flight request.
+///
+/// Its params name the request to cancel, as in `{"id": 1}`.
+pub const CANCEL_METHOD: &str = "$/cancelRequest";
+
+/// Name of the request member that carries the client's timeout.
+///
+/// The value is a number of milliseconds, as in `"timeoutMs": 500`. The
+/// request is answered with a "Request timed out" error once it passes, or
+/// once the server's own request timeout passes, whichever comes first.
+pub const TIMEOUT_FIELD: &str = "timeoutMs";
+
+/// Type alias for callbacks that receive the method name of an unhandled
+/// notification.
+type BoxedNotificationHook = Box<dyn Fn(&str) + Send + Sync>;
+
+/// Type alias for callbacks that receive a failed notification's method name
+/// and the error its handler returned.
+type BoxedNotificationErrorHook = Box<dyn Fn(&str, &Error) + Send + Sync>;
+
+/// Type alias for functions that build the error for an unknown method.
+type BoxedMethodNotFound = Box<dyn Fn(&str) -> crate::types::Error + Send + Sync>;
+
+/// Metadata attached to a response by a response hook, as name and value
+/// pairs.
+///
+/// Transport integrations decide how to deliver it. The axum handler sends
+/// each pair as an HTTP response header.
+pub type ResponseMetadata = Vec<(String, String)>;
+
+/// The kind of registration that handles a method name.
+///
+/// When more than one registration could handle the same method name,
+/// `JsonRpc` picks the handler with a fixed precedence. The variants are listed
+/// from highest to lowest precedence, so an exact method always wins over an
+/// alias, an alias over a prefix, and a prefix over the fallback:
+///
+/// - `Exact`: a method registered with [`JsonRpc::add`]
+/// - `Alias`: a name registered with [`JsonRpc::alias`]
+/// - `Prefix`: the longest matching prefix from [`JsonRpc::add_prefix`]
+/// - `Fallback`: the catch-all from [`JsonRpc::add_fallback`]
+///
+/// Use [`JsonRpc::resolve`] to see which kind handles a given method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    Exact,
+    Alias,
+    Prefix,
+    Fallback,
+}
+
+/// How batch responses are framed on the wire.
+///
+/// Set with [`JsonRpc::with_batch_format`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BatchFormat {
+    /// A single JSON array of responses, as the JSON-RPC 2.0 specification
+    /// requires.
+    #[default]
+    Array,
+    /// One response object per line, separated by `\n`.
+    Ndjson,
+}
+
+/// A summary of what a call produced, returned by
+/// [`JsonRpc::call_with_outcome`].
+///
+/// For a batch, `is_error` is `true` if any response in it is an error, and
+/// `code` is the code of the first error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallOutcome {
+    /// Whether the response is, or a batch contains, an error response.
+    pub is_error: bool,
+    /// The error code, if the response is an error.
+    pub code: Option<i32>,
+    /// Whether no response was produced, because the message was a
+    /// notification or a batch of notifications.
+    pub is_notification: bool,
+}
+
+impl CallOutcome {
+    const NOTIFICATION: Self = Self {
+        is_error: false,
+        code: None,
+        is_notification: true,
+    };
+
+    /// Summarize the responses about to be sent.
+    fn of(output: &Output) -> Self {
+        let code = match output {
+            Output::Single(response) => response.error.as_ref().map(|e| e.code),
+            Output::Batch(entries) => entries
+                .iter()
+                .find_map(|entry| entry.response.error.as_ref().map(|e| e.code)),
+        };
+        Self {
+            is_error: code.is_some(),
+            code,
+            is_notification: false,
+        }
+    }
+}
+
+/// Success and error counts for one method.
+///
+/// Returned by [`JsonRpc::stats`]. A call counts as an error when its handler
+/// returns `Err`, including parameter deserialization failures. Requests for
+/// unknown methods are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MethodStats {
+    /// Number of handler calls that returned `Ok`.
+    pub success: u64,
+    /// Number of handler calls that returned `Err`.
+    pub error: u64,
+}
+
+/// Details of the message a handler registered with
+/// [`JsonRpc::add_with_context`] is handling.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// The method name from the message, as the client sent it.
+    pub method: String,
+    /// The request id, or `None` for a notification.
+    pub id: Option<RequestId>,
+    cancellation: CancellationToken,
+    notifications: NotificationSink,
+}
+
+impl RequestContext {
+    /// Return the token that a `$/cancelRequest` notification for this
+    /// request triggers.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Return the sink for notifications to the client of this call.
+    pub fn notifications(&self) -> &NotificationSink {
+        &self.notifications
+    }
+}
+
+/// Wire messages for errors that `JsonRpc` reports before any handler runs.
+///
+/// The JSON-RPC 2.0 specification fixes the error codes for malformed input,
+/// but not the human-readable messages. Use [`JsonRpc::with_error_messages`]
+/// to replace the defaults, for example for localization. The codes stay
+/// -32700 for parse errors and -32600 for invalid requests.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::{ErrorMessages, JsonRpc};
+///
+/// let json_rpc = JsonRpc::new().with_error_messages(ErrorMessages {
+///     parse_error: "Erreur d'analyse".to_string(),
+///     ..ErrorMessages::default()
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorMessages {
+    /// Message for -32700 errors. Defaults to `"Parse error"`.
+    pub parse_error: String,
+    /// Message for -32600 errors. Defaults to `"Invalid Request"`.
+    pub invalid_request: String,
+}
+
+impl Default for ErrorMessages {
+    fn default() -> Self {
+        Self {
+            parse_error: "Parse error".to_string(),
+            invalid_request: "Invalid Request".to_string(),
+        }
+    }
+}
+
+impl ErrorMessages {
+    /// Build a -32700 wire error with the configured message.
+    pub fn parse_error(&self) -> crate::types::Error {
+        crate::types::Error::parse_error(self.parse_error.clone())
+    }
+
+    /// Build a -32600 wire error with the configured message.
+    pub fn invalid_request(&self) -> crate::types::Error {
+        crate::types::Error::invalid_request(self.invalid_request.clone())
+    }
+}
+
+/// State that belongs to one connection of a serve loop.
+#[derive(Default)]
+pub(crate) struct Connection {
+    /// Whether the initialize handshake completed on this connection.
+    initialized: AtomicBool,
+    /// Cancellation tokens of the requests running on this connection.
+    in_flight: Arc<InFlightRequests>,
+    /// Where batch responses go one by one with
+    /// [`JsonRpc::stream_batch_responses`].
+    batch_responses: Option<mpsc::UnboundedSender<String>>,
+}
+
+impl Connection {
+    /// Create the state of a connection that sends streamed batch responses
+    /// to `batch_responses`.
+    pub(crate) fn new(batch_responses: mpsc::UnboundedSender<String>) -> Self {
+        Self {
+            batch_responses: Some(batch_responses),
+            ..Self::default()
+        }
+    }
+}
+
+tokio::task_local! {
+    /// The connection the current task is serving.
+    pub(crate) static CONNECTION: Arc<Connection>;
+}
+
+/// The responses produced by processing one message.
+enum Output {
+    Single(Response),
+    Batch(Vec<BatchEntry>),
+}
+
+/// A batch response together with the member echoed from its request.
+struct BatchEntry {
+    response: Response,
+    echo: Option<(String, serde_json::Value)>,
+}
+
+impl Serialize for BatchEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some((name, value)) = &self.echo else {
+            return self.response.serialize(serializer);
+        };
+        let response = &self.response;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("jsonrpc", &response.jsonrpc)?;
+        if let Some(raw) = &response.raw_result {
+            map.serialize_entry("result", raw)?;
+        } else if let Some(result) = &response.result {
+            map.serialize_entry("result", result)?;
+        }
+        if let Some(error) = &response.error {
+            map.serialize_entry("error", error)?;
+        }
+        map.serialize_entry("id", &response.id)?;
+        map.serialize_entry(name, value)?;
+        map.end()
+    }
+}
+
+/// A handler selected by method lookup.
+enum Route<'a> {
+    Method(&'a BoxedHandler),
+    Contextual(&'a BoxedContextHandler),
+    Fallback(&'a BoxedFallback),
+}
+
 /// JSON-RPC handler for message processing.
 ///
 /// `JsonRpc` registers method handlers and processes JSON-RPC messages via the
@@ -47,13 +345,76 @@ type BoxedHandler = Box<
 /// ```
 pub struct JsonRpc {
     handlers: HashMap<String, BoxedHandler>,
+    contextual: HashMap<String, BoxedContextHandler>,
+    in_flight: Arc<InFlightRequests>,
+    message_handlers: HashMap<String, BoxedMessageHandler>,
+    aliases: HashMap<String, String>,
+    prefixes: Vec<(String, BoxedHandler)>,
+    fallback: Option<BoxedFallback>,
+    parser: Box<dyn JsonParser>,
+    idempotency: Option<IdempotencyCache>,
+    stats: Mutex<HashMap<String, MethodStats>>,
+    error_messages: ErrorMessages,
+    verbose_errors: bool,
+    response_hook: Option<BoxedResponseHook>,
+    gates: HashMap<String, String>,
+    flags: RwLock<HashMap<String, bool>>,
+    spec_check: bool,
+    batch_echo: Option<String>,
+    batch_format: BatchFormat,
+    batch_timeout: Option<Duration>,
+    stream_batch_responses: bool,
+    slow_threshold: Option<Duration>,
+    request_timeout: Option<Duration>,
+    concurrent_requests: usize,
+    redactions: HashMap<String, Vec<String>>,
+    signatures: HashMap<String, (String, String)>,
+    unknown_notifications: AtomicU64,
+    dead_letters: Option<(usize, Mutex<VecDeque<Notification>>)>,
+    middleware: Vec<Box<dyn BoxedMiddleware>>,
+    unhandled_notification_hook: Option<BoxedNotificationHook>,
+    method_not_found: Option<BoxedMethodNotFound>,
+    notification_error_hook: Option<BoxedNotificationErrorHook>,
+    initialize: Option<(String, AtomicBool)>,
 }
 
 impl JsonRpc {
     /// Create a new empty JSON-RPC handler.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             handlers: HashMap::new(),
+            contextual: HashMap::new(),
+            in_flight: Arc::default(),
+            message_handlers: HashMap::new(),
+            aliases: HashMap::new(),
+            prefixes: Vec::new(),
+            fallback: None,
+            parser: Box::new(SerdeJsonParser),
+            idempotency: None,
+            stats: Mutex::new(HashMap::new()),
+            error_messages: ErrorMessages::default(),
+            verbose_errors: false,
+            response_hook: None,
+            gates: HashMap::new(),
+            flags: RwLock::new(HashMap::new()),
+            spec_check: false,
+            batch_echo: None,
+            batch_format: BatchFormat::Array,
+            batch_timeout: None,
+            stream_batch_responses: false,
+            slow_threshold: None,
+            request_timeout: None,
+            concurrent_requests: 1,
+            redactions: HashMap::new(),
+            signatures: HashMap::new(),
+            unknown_notifications: AtomicU64::new(0),
+            dead_letters: None,
+            middleware: Vec::new(),
+            unhandled_notification_hook: None,
+            method_not_found: None,
+            notification_error_hook: None,
+            initialize: None,
         }
     }
 
@@ -61,6 +422,8 @@ impl JsonRpc {
     ///
     /// The handler must be an async function that takes deserialized parameters
     /// and returns a `Result` with either the return value or an `Error`.
+    /// Registering a method name again replaces the earlier handler; use
+    /// [`JsonRpc::try_add`] to reject duplicates instead.
     ///
     /// # Example
     ///
@@ -82,186 +445,2342 @@ impl JsonRpc {
         P: serde::de::DeserializeOwned + Send + Sync + 'static,
         R: Serialize + Send + Sync + 'static,
     {
-        let handler = Arc::new(handler);
-        let boxed: BoxedHandler = Box::new(move |params: serde_json::Value| {
-            let handler = Arc::clone(&handler);
-            Box::pin(async move {
-                let parsed: P = serde_json::from_value(params)?;
-                let result = handler(parsed).await?;
-                Ok(serde_json::to_value(result)?)
-            })
-        });
-
-        self.handlers.insert(method.to_string(), boxed);
+        self.insert(method, handler);
         self
     }
 
-    /// Process a JSON-RPC message and return the response JSON string (if any).
+    /// Register a method handler, failing if the method already exists.
     ///
-    /// This method processes a JSON-RPC message string and returns the response.
-    /// It handles:
+    /// [`JsonRpc::add`] silently replaces an earlier handler registered under
+    /// the same name, which can hide configuration bugs. This returns a
+    /// protocol error instead when `method` already has a handler of any kind.
     ///
-    /// - JSON parsing and validation
-    /// - Message type detection (request, notification, batch, response)
-    /// - Method routing and execution
-    /// - Error handling and response generation
+    /// # Example
     ///
-    /// Returns `None` for notifications (which don't require a response).
-    pub async fn call(&self, json_str: &str) -> Option<String> {
-        let value: serde_json::Value = match serde_json::from_str(json_str) {
-            Ok(v) => v,
-            Err(_) => {
-                let error = crate::types::Error::parse_error("Parse error");
-                let response = Response::error(RequestId::Null, error);
-                match serde_json::to_string(&response) {
-                    Ok(s) => return Some(s),
-                    Err(e) => {
-                        tracing::error!("Failed to serialize parse error response: {}", e);
-                        return None;
-                    }
-                }
-            }
-        };
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn ping(_params: ()) -> Result<&'static str, json_rpc::Error> {
+    ///     Ok("pong")
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().try_add("ping", ping).unwrap();
+    /// assert!(json_rpc.try_add("ping", ping).is_err());
+    /// ```
+    pub fn try_add<F, P, R, Fut>(self, method: &str, handler: F) -> Result<Self, Error>
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        if self.is_registered(method) {
+            return Err(Error::protocol(format!(
+                "Method already registered: {}",
+                method
+            )));
+        }
+        Ok(self.add(method, handler))
+    }
 
-        let request_id = value.get("id").and_then(|id_value| match id_value {
-            serde_json::Value::Null => Some(RequestId::Null),
-            serde_json::Value::Number(n) => n.as_u64().map(RequestId::Number),
-            serde_json::Value::String(s) => Some(RequestId::String(s.clone())),
-            _ => None,
-        });
+    /// Register a method handler, replacing any earlier one.
+    ///
+    /// This behaves like [`JsonRpc::add`] and states the intent to override
+    /// an existing registration explicitly.
+    pub fn add_or_replace<F, P, R, Fut>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        self.add(method, handler)
+    }
 
-        let message = match Message::from_json(value) {
-            Ok(msg) => msg,
-            Err(Error::InvalidRequest(_)) => {
-                let error = crate::types::Error::invalid_request("Invalid Request");
-                let id_to_use = request_id.unwrap_or(RequestId::Null);
-                let response = Response::error(id_to_use, error);
-                match serde_json::to_string(&response) {
-                    Ok(s) => return Some(s),
-                    Err(e) => {
-                        tracing::error!("Failed to serialize invalid request response: {}", e);
-                        return None;
-                    }
-                }
-            }
-            Err(_) => {
-                let error = crate::types::Error::internal_error("Internal error");
-                let response = Response::error(request_id.unwrap_or(RequestId::Null), error);
-                match serde_json::to_string(&response) {
-                    Ok(s) => return Some(s),
-                    Err(e) => {
-                        tracing::error!("Failed to serialize internal error response: {}", e);
-                        return None;
-                    }
-                }
-            }
-        };
+    /// Remove every exact registration of `method`, so the handler about to
+    /// be registered is the only one for that name.
+    fn unregister(&mut self, method: &str) {
+        self.handlers.remove(method);
+        self.contextual.remove(method);
+        self.message_handlers.remove(method);
+    }
 
-        match message {
-            Message::Request(request) => {
-                let method_name = &request.method;
-                let params = request.params.unwrap_or(serde_json::Value::Null);
-                let request_id = request.id.clone();
-                let response = if let Some(handler) = self.handlers.get(method_name) {
-                    let result = handler(params).await;
-                    match result {
-                        Ok(result_value) => Response::success(request_id, result_value),
-                        Err(e) => {
-                            let error = match e {
-                                crate::error::Error::RpcError { code, message } => {
-                                    crate::types::Error::new(code, message, None)
-                                }
-                                _ => crate::types::Error::new(-32603, e.to_string(), None),
-                            };
-                            Response::error(request_id, error)
-                        }
-                    }
-                } else {
-                    let error = crate::types::Error::method_not_found(format!(
-                        "Unknown method: {}",
-                        method_name
-                    ));
-                    Response::error(request_id, error)
-                };
-                match serde_json::to_string(&response) {
-                    Ok(s) => Some(s),
-                    Err(e) => {
-                        tracing::error!("Failed to serialize response: {}", e);
-                        None
-                    }
-                }
-            }
-            Message::Notification(notification) => {
-                if let Some(handler) = self.handlers.get(&notification.method) {
-                    let params = notification.params.unwrap_or(serde_json::Value::Null);
-                    let _ = handler(params).await;
-                }
-                None
-            }
-            Message::Batch(messages) => {
-                let mut responses = Vec::new();
-
-                for message in messages {
-                    match message {
-                        Message::Request(request) => {
-                            let method_name = &request.method;
-                            let params = request.params.unwrap_or(serde_json::Value::Null);
-                            let id = request.id;
-                            let response = if let Some(handler) = self.handlers.get(method_name) {
-                                let result = handler(params).await;
-                                match result {
-                                    Ok(result_value) => Response::success(id, result_value),
-                                    Err(e) => {
-                                        let error = match e {
-                                            crate::error::Error::RpcError { code, message } => {
-                                                crate::types::Error::new(code, message, None)
-                                            }
-                                            _ => crate::types::Error::new(
-                                                -32603,
-                                                e.to_string(),
-                                                None,
-                                            ),
-                                        };
-                                        Response::error(id, error)
-                                    }
-                                }
-                            } else {
-                                let error = crate::types::Error::method_not_found(format!(
-                                    "Unknown method: {}",
-                                    method_name
-                                ));
-                                Response::error(id, error)
-                            };
-                            responses.push(response);
-                        }
-                        Message::Notification(notification) => {
-                            if let Some(handler) = self.handlers.get(&notification.method) {
-                                let params = notification.params.unwrap_or(serde_json::Value::Null);
-                                let _ = handler(params).await;
-                            }
-                        }
-                        Message::Response(response) => {
-                            responses.push(response);
-                        }
-                        Message::Batch(_) => {
-                            let error_response = Response::error(
-                                crate::types::RequestId::Null,
-                                crate::types::Error::invalid_request("Invalid Request"),
-                            );
-                            responses.push(error_response);
-                        }
-                    }
-                }
+    /// Return `true` if `method` has an exact registration of any kind.
+    fn is_registered(&self, method: &str) -> bool {
+        self.handlers.contains_key(method)
+            || self.contextual.contains_key(method)
+            || self.message_handlers.contains_key(method)
+    }
 
-                match serde_json::to_string(&responses) {
-                    Ok(s) => Some(s),
-                    Err(e) => {
-                        tracing::error!("Failed to serialize batch responses: {}", e);
-                        None
-                    }
-                }
-            }
-            Message::Response(_response) => None,
+    /// Add the methods of `other` to this handler, failing on a conflict.
+    ///
+    /// This composes a service from modules that each build their own
+    /// `JsonRpc`. The exact methods, aliases and prefix handlers of `other`
+    /// are added, along with their signatures, redactions and feature flag
+    /// gates. Everything else, such as timeouts, middleware and hooks, stays
+    /// as configured on `self`. Returns a protocol error naming a method if
+    /// both handlers register it; use [`JsonRpc::merge_or_replace`] to let
+    /// `other` win instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn ping(_params: ()) -> Result<&'static str, json_rpc::Error> {
+    ///     Ok("pong")
+    /// }
+    ///
+    /// async fn shutdown(_params: ()) -> Result<bool, json_rpc::Error> {
+    ///     Ok(true)
+    /// }
+    ///
+    /// let core = JsonRpc::new().add("ping", ping);
+    /// let admin = JsonRpc::new().add("admin.shutdown", shutdown);
+    /// let json_rpc = core.merge(admin).unwrap();
+    /// assert!(json_rpc.merge(JsonRpc::new().add("ping", ping)).is_err());
+    /// ```
+    pub fn merge(self, other: JsonRpc) -> Result<Self, Error> {
+        let conflict = other
+            .handlers
+            .keys()
+            .chain(other.contextual.keys())
+            .chain(other.message_handlers.keys())
+            .filter(|method| self.is_registered(method))
+            .min();
+        if let Some(method) = conflict {
+            return Err(Error::protocol(format!(
+                "Method already registered: {}",
+                method
+            )));
+        }
+        Ok(self.merge_or_replace(other))
+    }
+
+    /// Add the methods of `other` to this handler, replacing conflicts.
+    ///
+    /// This works like [`JsonRpc::merge`], except that a method, alias or
+    /// prefix registered by both handlers keeps the registration from
+    /// `other`.
+    pub fn merge_or_replace(mut self, other: JsonRpc) -> Self {
+        for method in other
+            .handlers
+            .keys()
+            .chain(other.contextual.keys())
+            .chain(other.message_handlers.keys())
+        {
+            self.unregister(method);
+        }
+        self.handlers.extend(other.handlers);
+        self.contextual.extend(other.contextual);
+        self.message_handlers.extend(other.message_handlers);
+        self.aliases.extend(other.aliases);
+        for (prefix, handler) in other.prefixes {
+            self.prefixes.retain(|(existing, _)| *existing != prefix);
+            self.prefixes.push((prefix, handler));
         }
+        self.signatures.extend(other.signatures);
+        self.redactions.extend(other.redactions);
+        self.gates.extend(other.gates);
+        self
+    }
+
+    /// Register a JSON-RPC method handler in place.
+    ///
+    /// This is the non-consuming form of [`JsonRpc::add`], for registering
+    /// methods in a loop or under a condition without reassigning the
+    /// handler.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// let mut json_rpc = JsonRpc::new();
+    /// for (name, value) in [("one", 1), ("two", 2)] {
+    ///     json_rpc.insert(name, move |_params: ()| async move { Ok::<_, json_rpc::Error>(value) });
+    /// }
+    /// ```
+    pub fn insert<F, P, R, Fut>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        self.unregister(method);
+        self.handlers
+            .insert(method.to_string(), box_handler(handler));
+        self.record_signature::<P, R>(method);
+        self
+    }
+
+    /// Register a method handler with its own result serialization.
+    ///
+    /// `serialize` replaces the default `serde_json::to_value` step for this
+    /// method only. Use it when a result type needs a different JSON form in
+    /// responses than its `Serialize` implementation gives, such as a date
+    /// that should be sent as an RFC 3339 string, without wrapping the type.
+    /// The result type does not need to implement `Serialize`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    /// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    ///
+    /// async fn now(_params: ()) -> Result<SystemTime, json_rpc::Error> {
+    ///     Ok(SystemTime::now())
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_with_serializer("now", now, |time: &SystemTime| {
+    ///     let millis = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_millis();
+    ///     Ok(serde_json::json!(millis as u64))
+    /// });
+    /// ```
+    pub fn add_with_serializer<F, P, R, Fut, S>(
+        mut self,
+        method: &str,
+        handler: F,
+        serialize: S,
+    ) -> Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Send + Sync + 'static,
+        S: Fn(&R) -> Result<serde_json::Value, Error> + Send + Sync + 'static,
+    {
+        let serialize = move |result: &R| serialize(result).map(ResultValue::Value);
+        self.unregister(method);
+        self.handlers
+            .insert(method.to_string(), box_handler_with(handler, serialize));
+        self.record_signature::<P, R>(method);
+        self
+    }
+
+    /// Register a handler that can be cancelled while it runs.
+    ///
+    /// The handler receives the deserialized parameters and a
+    /// [`CancellationToken`] for the request. A `$/cancelRequest`
+    /// notification whose params name the request id, as in `{"id": 1}`,
+    /// triggers the token. Cancellation is cooperative: the handler checks the
+    /// token and returns [`Error::Cancelled`], which is sent as a "Request
+    /// cancelled" error with code `-32800`.
+    ///
+    /// The cancel notification must be processed while the request is still
+    /// running, so the transport has to call the handler concurrently. The
+    /// serve loops in the [`io`](crate::io) module do so once
+    /// [`JsonRpc::with_concurrent_requests`] allows more than one request at
+    /// a time, and a cancel notification only reaches requests of its own
+    /// connection. Messages passed to [`JsonRpc::call`] outside a serve loop,
+    /// for example by spawning each call on a shared `Arc<JsonRpc>`, share
+    /// one set of request ids, so such a transport must keep clients from
+    /// cancelling each other. Notifications get a token that is never
+    /// triggered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    /// use json_rpc::cancellation::CancellationToken;
+    ///
+    /// async fn count(limit: u64, token: CancellationToken) -> Result<u64, json_rpc::Error> {
+    ///     for n in 0..limit {
+    ///         if token.is_cancelled() {
+    ///             return Err(json_rpc::Error::Cancelled);
+    ///         }
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     Ok(limit)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_cancellable("count", count);
+    /// ```
+    pub fn add_cancellable<F, P, R, Fut>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P, CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        self.add_with_context(method, move |params: P, context: RequestContext| {
+            handler(params, context.cancellation().clone())
+        })
     }
+
+    /// Register a handler that can send notifications while it runs.
+    ///
+    /// The handler receives the deserialized parameters and a
+    /// [`NotificationSink`]. Notifications sent through it, such as progress
+    /// updates, are written to the client before the final response when the
+    /// message is served by [`serve_io`](crate::serve_io) or another caller of
+    /// [`JsonRpc::call_with_sink`]. With [`JsonRpc::call`], they are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    /// use json_rpc::sink::NotificationSink;
+    ///
+    /// async fn build(steps: u32, sink: NotificationSink) -> Result<&'static str, json_rpc::Error> {
+    ///     for step in 1..=steps {
+    ///         sink.send("progress", step)?;
+    ///     }
+    ///     Ok("done")
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_with_sink("build", build);
+    /// ```
+    pub fn add_with_sink<F, P, R, Fut>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P, NotificationSink) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        self.add_with_context(method, move |params: P, context: RequestContext| {
+            handler(params, context.notifications().clone())
+        })
+    }
+
+    /// Register a handler that also receives the [`RequestContext`].
+    ///
+    /// The context carries the method name the client called and the request
+    /// id, so one handler can serve several method names or log the id. The
+    /// id is `None` for notifications.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::{JsonRpc, RequestContext};
+    ///
+    /// async fn whoami(_params: (), context: RequestContext) -> Result<String, json_rpc::Error> {
+    ///     Ok(context.method)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .add_with_context("ping", whoami)
+    ///     .add_with_context("pong", whoami);
+    /// ```
+    pub fn add_with_context<F, P, R, Fut>(mut self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.unregister(method);
+        self.contextual.insert(
+            method.to_string(),
+            Box::new(move |params: serde_json::Value, context| {
+                let handler = Arc::clone(&handler);
+                Box::pin(async move {
+                    let parsed: P = parse_params(params)?;
+                    let result = handler(parsed, context).await?;
+                    ResultValue::from_result(&result)
+                })
+            }),
+        );
+        self.record_signature::<P, R>(method);
+        self
+    }
+
+    /// Trigger the cancellation token of an in-flight request.
+    ///
+    /// This is what a `$/cancelRequest` notification does. Inside a serve loop
+    /// it looks at the requests of the connection being served, otherwise at
+    /// the requests passed to [`JsonRpc::call`] outside any serve loop.
+    /// Returns `false` if no cancellable request with that id is running.
+    pub fn cancel(&self, id: &RequestId) -> bool {
+        let in_flight = self.in_flight();
+        let in_flight = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        match in_flight.get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the running requests that cancellation looks at: those of the
+    /// connection being served, or this handler's own outside a serve loop.
+    fn in_flight(&self) -> Arc<InFlightRequests> {
+        CONNECTION
+            .try_with(|connection| Arc::clone(&connection.in_flight))
+            .unwrap_or_else(|_| Arc::clone(&self.in_flight))
+    }
+
+    /// Register a low-level handler that receives the whole message.
+    ///
+    /// The handler gets the [`Message`] as it was received, either a
+    /// [`Message::Request`] or a [`Message::Notification`], with the exact id
+    /// and undecoded params, and builds the [`Response`] itself. This suits
+    /// protocol bridges that forward messages rather than decode them.
+    ///
+    /// A message handler takes precedence over every other registration for
+    /// its method. Its response is sent as returned, so keep the request id in
+    /// it. Returning `None` for a request sends an internal error instead;
+    /// the return value for a notification is discarded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::{JsonRpc, Message, Response};
+    ///
+    /// let json_rpc = JsonRpc::new().add_message_handler("bridge", |message: Message| async move {
+    ///     match message {
+    ///         Message::Request(request) => Some(Response::success(request.id, serde_json::json!("forwarded"))),
+    ///         _ => None,
+    ///     }
+    /// });
+    /// ```
+    pub fn add_message_handler<F, Fut>(mut self, method: &str, handler: F) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<Response>> + Send + 'static,
+    {
+        self.unregister(method);
+        self.message_handlers.insert(
+            method.to_string(),
+            Box::new(move |message| Box::pin(handler(message))),
+        );
+        self
+    }
+
+    /// Register a synchronous handler that runs on the blocking thread pool.
+    ///
+    /// Use this for CPU-bound or otherwise blocking logic. Each call runs the
+    /// handler with `tokio::task::spawn_blocking`, so it does not stall the
+    /// async runtime while other requests are processed. If the handler
+    /// panics, the request fails with an internal error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// fn fibonacci(n: u64) -> Result<u64, json_rpc::Error> {
+    ///     Ok(if n < 2 { n } else { fibonacci(n - 1)? + fibonacci(n - 2)? })
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_blocking("fibonacci", fibonacci);
+    /// ```
+    pub fn add_blocking<F, P, R>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P) -> Result<R, Error> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.add(method, move |params: P| {
+            let handler = Arc::clone(&handler);
+            async move {
+                tokio::task::spawn_blocking(move || handler(params))
+                    .await
+                    .map_err(|e| Error::protocol(format!("Blocking handler failed: {}", e)))?
+            }
+        })
+    }
+
+    /// Register a handler that takes named params only.
+    ///
+    /// [`JsonRpc::add`] deserializes whatever params arrive, so a struct
+    /// parameter type also accepts an array with its fields in declaration
+    /// order. A named handler binds params by name only: the params must be
+    /// an object, or omitted, which counts as an empty object. Fields marked
+    /// `#[serde(default)]` may be left out. Array or scalar params are
+    /// rejected with an Invalid params error (`-32602`).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     query: String,
+    ///     #[serde(default)]
+    ///     limit: Option<u32>,
+    /// }
+    ///
+    /// async fn search(params: Search) -> Result<Vec<String>, json_rpc::Error> {
+    ///     Ok(vec![params.query; params.limit.unwrap_or(1) as usize])
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_named("search", search);
+    /// ```
+    pub fn add_named<F, P, R, Fut>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let mut json_rpc = self.add(method, move |params: serde_json::Value| {
+            let handler = Arc::clone(&handler);
+            async move {
+                let params = match params {
+                    serde_json::Value::Null => serde_json::Value::Object(serde_json::Map::new()),
+                    serde_json::Value::Object(_) => params,
+                    _ => {
+                        return Err(Error::InvalidParams(serde::de::Error::custom(
+                            "expected named params as an object",
+                        )));
+                    }
+                };
+                handler(parse_params::<P>(params)?).await
+            }
+        });
+        json_rpc.record_signature::<P, R>(method);
+        json_rpc
+    }
+
+    /// Register a handler whose params are checked by `validate` first.
+    ///
+    /// Serde only checks that params have the right shape. `validate` runs
+    /// on the deserialized params before the handler and checks their
+    /// values, such as ranges or non-empty strings. When it returns
+    /// `Err(message)`, the request gets an Invalid params error (`-32602`)
+    /// with that message and the handler does not run.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn sqrt(x: f64) -> Result<f64, json_rpc::Error> {
+    ///     Ok(x.sqrt())
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_validated(
+    ///     "sqrt",
+    ///     |x: &f64| if *x >= 0.0 { Ok(()) } else { Err("x must not be negative".to_string()) },
+    ///     sqrt,
+    /// );
+    /// ```
+    pub fn add_validated<F, P, R, Fut, V>(self, method: &str, validate: V, handler: F) -> Self
+    where
+        V: Fn(&P) -> Result<(), String> + Send + Sync + 'static,
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        self.add(method, move |params: P| {
+            let call = validate(&params).map(|()| handler(params));
+            async move {
+                match call {
+                    Ok(call) => call.await,
+                    Err(message) => Err(Error::invalid_params(message)),
+                }
+            }
+        })
+    }
+
+    /// Register a handler that builds its result object field by field.
+    ///
+    /// The handler receives the deserialized parameters and an empty
+    /// [`ResultBuilder`]. It sets named fields as its sub-steps finish and
+    /// returns the builder, which is sent as the result object.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::{JsonRpc, ResultBuilder};
+    ///
+    /// async fn profile(
+    ///     user: String,
+    ///     mut result: ResultBuilder,
+    /// ) -> Result<ResultBuilder, json_rpc::Error> {
+    ///     result.set("name", &user)?;
+    ///     result.set("posts", 3)?;
+    ///     Ok(result)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_with_builder("profile", profile);
+    /// ```
+    pub fn add_with_builder<F, P, Fut>(self, method: &str, handler: F) -> Self
+    where
+        F: Fn(P, ResultBuilder) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ResultBuilder, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+    {
+        self.add(method, move |params: P| {
+            handler(params, ResultBuilder::new())
+        })
+    }
+
+    /// Use a custom parser for incoming JSON strings.
+    ///
+    /// By default `JsonRpc` parses messages with `serde_json`. Replace it with a
+    /// [`JsonParser`] implementation backed by another library, such as
+    /// `simd-json`. Handlers still receive `serde_json::Value` based
+    /// parameters.
+    pub fn with_parser(mut self, parser: impl JsonParser + 'static) -> Self {
+        self.parser = Box::new(parser);
+        self
+    }
+
+    /// Cache responses of requests that carry an idempotency key.
+    ///
+    /// When a request includes an `idempotencyKey` member, its response is
+    /// cached for `ttl`. A later request with the same key, method and params
+    /// gets the cached response without running the handler again, and one
+    /// that arrives while the first is still running waits for its response.
+    /// This protects handlers from duplicate delivery on at-least-once
+    /// transports. Requests without a key are not affected. See the [`idempotency`](crate::idempotency)
+    /// module for details.
+    pub fn with_idempotency(mut self, ttl: Duration) -> Self {
+        self.idempotency = Some(IdempotencyCache::new(ttl));
+        self
+    }
+
+    /// Replace the messages used for parse and invalid request errors.
+    ///
+    /// Only the `message` field of the error object changes. The error codes
+    /// stay as defined by the JSON-RPC 2.0 specification.
+    pub fn with_error_messages(mut self, messages: ErrorMessages) -> Self {
+        self.error_messages = messages;
+        self
+    }
+
+    /// Build the error object for requests to unknown methods with `build`.
+    ///
+    /// By default such requests get `-32601 "Unknown method: <name>"`.
+    /// `build` receives the requested method name and returns the error to
+    /// send instead, for example a generic message that does not echo the
+    /// name back to the client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::{JsonRpc, types};
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .with_method_not_found(|_method| types::Error::method_not_found("Not found"));
+    /// ```
+    pub fn with_method_not_found<F>(mut self, build: F) -> Self
+    where
+        F: Fn(&str) -> crate::types::Error + Send + Sync + 'static,
+    {
+        self.method_not_found = Some(Box::new(build));
+        self
+    }
+
+    /// Attach serde error details to the `data` field of error responses.
+    ///
+    /// When a handler fails with a deserialization error, such as params that
+    /// do not match the handler's parameter type, the error message alone
+    /// often hides what went wrong. In verbose mode the error object also
+    /// carries a `data` object with the serde error `category` (`"io"`,
+    /// `"syntax"`, `"data"` or `"eof"`), the `line` and `column` where it
+    /// occurred, and the full `message`:
+    ///
+    /// ```json
+    /// {"code":-32602,"message":"Invalid params","data":{"category":"data","line":0,"column":0,"message":"..."}}
+    /// ```
+    ///
+    /// Without verbose mode, invalid params errors carry only the serde
+    /// message as a string in `data`.
+    ///
+    /// Params are deserialized from an already parsed value, so `line` and
+    /// `column` are `0` unless the handler parsed JSON text itself. Verbose
+    /// mode is off by default because the details may reveal internals.
+    pub fn with_verbose_errors(mut self, verbose: bool) -> Self {
+        self.verbose_errors = verbose;
+        self
+    }
+
+    /// Inspect every serialized response before it is sent.
+    ///
+    /// The hook receives the exact bytes of the response that
+    /// [`JsonRpc::call_with_metadata`] returns and can compute metadata over
+    /// them, such as an HMAC signature for signed-response protocols. The
+    /// returned pairs are passed back alongside the response, and the axum
+    /// handler sends them as HTTP headers. The hook is not run for
+    /// notifications, which have no response, or by
+    /// [`JsonRpc::call_to_writer`], which never holds the full response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// fn sign(bytes: &[u8]) -> String {
+    ///     // Compute an HMAC over the bytes with your key.
+    ///     format!("{}", bytes.len())
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .on_response(|bytes| vec![("x-signature".to_string(), sign(bytes))]);
+    /// ```
+    pub fn on_response<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&[u8]) -> ResponseMetadata + Send + Sync + 'static,
+    {
+        self.response_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Check every outgoing response against the JSON-RPC 2.0 specification.
+    ///
+    /// This is a diagnostic mode for development. Each response is checked
+    /// before it is sent: it must carry `"jsonrpc": "2.0"`, exactly one of
+    /// `result` and `error`, and a non-null `id` unless it is an error. A
+    /// violation is logged at error level and the response is replaced by an
+    /// internal error, so clients never see the malformed output.
+    pub fn with_spec_check(mut self, enabled: bool) -> Self {
+        self.spec_check = enabled;
+        self
+    }
+
+    /// Copy a member of each batch entry into its response.
+    ///
+    /// Some clients tag every batch entry with their own reference, such as a
+    /// `clientRef` member, and match responses by it instead of by id. When
+    /// a batch entry carries the `member`, its value is added unchanged to the
+    /// response for that entry. Entries without the member, notifications, and
+    /// single requests are not affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+    ///     Ok(params)
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let json_rpc = JsonRpc::new().add("echo", echo).with_batch_echo("clientRef");
+    /// let response = json_rpc
+    ///     .call(r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":1,"clientRef":"a"}]"#)
+    ///     .await;
+    /// assert_eq!(
+    ///     response.as_deref(),
+    ///     Some(r#"[{"jsonrpc":"2.0","result":1,"id":1,"clientRef":"a"}]"#)
+    /// );
+    /// # });
+    /// ```
+    pub fn with_batch_echo(mut self, member: &str) -> Self {
+        self.batch_echo = Some(member.to_string());
+        self
+    }
+
+    /// Run `middleware` around the handling of every request.
+    ///
+    /// Middleware runs in registration order, so the first one registered is
+    /// the outermost and sees each request first. It wraps method routing and
+    /// the idempotency cache, so a cached response is only returned to
+    /// requests the middleware lets through. Notifications do not pass
+    /// through middleware.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::middleware::{Middleware, Next};
+    /// use json_rpc::types::Error;
+    /// use json_rpc::{JsonRpc, Request, Response};
+    ///
+    /// struct RequireToken;
+    ///
+    /// impl Middleware for RequireToken {
+    ///     async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+    ///         let token = request.params.as_ref().and_then(|p| p.get("token"));
+    ///         if token.is_none() {
+    ///             return Response::error(request.id, Error::new(-32099, "Unauthorized", None));
+    ///         }
+    ///         next.run(request).await
+    ///     }
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().with_middleware(RequireToken);
+    /// ```
+    pub fn with_middleware(mut self, middleware: impl Middleware) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Choose how batch responses are framed.
+    ///
+    /// With [`BatchFormat::Ndjson`], a batch response is sent as one response
+    /// object per line instead of a JSON array, for clients that read
+    /// newline-delimited responses. This is not part of JSON-RPC 2.0: clients
+    /// that follow the specification expect an array and will not parse it,
+    /// and over a newline-delimited transport every line looks like a
+    /// separate message. Only enable it when you control the clients. Single
+    /// responses are not affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::{BatchFormat, JsonRpc};
+    ///
+    /// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+    ///     Ok(params)
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let json_rpc = JsonRpc::new()
+    ///     .add("echo", echo)
+    ///     .with_batch_format(BatchFormat::Ndjson);
+    /// let response = json_rpc
+    ///     .call(r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":1},{"jsonrpc":"2.0","method":"echo","params":2,"id":2}]"#)
+    ///     .await;
+    /// assert_eq!(
+    ///     response.as_deref(),
+    ///     Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n{\"jsonrpc\":\"2.0\",\"result\":2,\"id\":2}")
+    /// );
+    /// # });
+    /// ```
+    pub fn with_batch_format(mut self, format: BatchFormat) -> Self {
+        self.batch_format = format;
+        self
+    }
+
+    /// Limit the total time spent processing one batch.
+    ///
+    /// All entries of a batch share a single deadline that starts when the
+    /// batch arrives. Once it passes, every request still running gets a
+    /// "Request timed out" error with code `-32001`, and the batch response
+    /// is returned with the results that finished in time. Notifications still
+    /// running at the deadline are cancelled.
+    pub fn with_batch_timeout(mut self, timeout: Duration) -> Self {
+        self.batch_timeout = Some(timeout);
+        self
+    }
+
+    /// Send each batch response as soon as it is ready.
+    ///
+    /// By default a batch is answered with one array once all of its entries
+    /// are done, so a fast entry waits for the slowest one. When enabled, the
+    /// serve loops in the [`io`](crate::io) module send every response of a
+    /// batch as its own message the moment it completes, in completion
+    /// order, and the client matches them to its requests by id. Batch echo
+    /// members and [`JsonRpc::with_spec_check`] still apply to each response,
+    /// and [`JsonRpc::with_batch_format`] does not.
+    ///
+    /// This only affects streaming transports. [`JsonRpc::call`] and the HTTP
+    /// integrations keep answering a batch with a single array.
+    pub fn stream_batch_responses(mut self, enabled: bool) -> Self {
+        self.stream_batch_responses = enabled;
+        self
+    }
+
+    /// Limit how long a single request may run.
+    ///
+    /// A request whose handler does not finish within `timeout` is cancelled
+    /// and answered with a "Request timed out" error with code `-32001`, so a
+    /// hanging handler cannot hold its caller forever. Notifications are
+    /// cancelled at the same limit. The limit applies to each batch entry on
+    /// its own, in addition to any [`JsonRpc::with_batch_timeout`]. Timed out
+    /// responses are not stored in the idempotency cache. A client can ask for
+    /// a tighter limit on one request with the [`TIMEOUT_FIELD`] member.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Let the serve loops handle up to `limit` messages of one connection at
+    /// the same time.
+    ///
+    /// By default the serve loops in the [`io`](crate::io) module handle one
+    /// message at a time and read the next one only after the previous
+    /// response is sent. With a higher limit they keep reading while requests
+    /// run and send each response as soon as it is ready, so responses can be
+    /// sent in a different order than their requests arrived. This is what
+    /// lets a `$/cancelRequest` notification reach a request that is still
+    /// running. Once `limit` messages are running, reading waits until one of
+    /// them finishes. A limit of `0` is treated as `1`.
+    ///
+    /// To send a response, the serve loop drops a pending
+    /// [`AsyncJsonRpcIo::recv`](crate::AsyncJsonRpcIo::recv) and calls it
+    /// again later, so a custom transport must keep a partly read message
+    /// between calls. [`LineIo`](crate::io::LineIo) and
+    /// [`ContentLengthIo`](crate::io::ContentLengthIo) do.
+    pub fn with_concurrent_requests(mut self, limit: usize) -> Self {
+        self.concurrent_requests = limit.max(1);
+        self
+    }
+
+    /// Return how many messages of one connection the serve loops handle at
+    /// the same time.
+    pub(crate) fn concurrent_requests(&self) -> usize {
+        self.concurrent_requests
+    }
+
+    /// Mask named params fields of `method` in log output.
+    ///
+    /// Requests are logged at debug level with their params, and slow
+    /// requests with a summary of them. Fields listed here are replaced by
+    /// `"***"` in those logs wherever they appear in the params object,
+    /// including nested objects. The handler still receives the real values.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn login(params: serde_json::Value) -> Result<bool, json_rpc::Error> {
+    ///     Ok(params["password"] == "hunter2")
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .add("login", login)
+    ///     .redact_params("login", &["password", "token"]);
+    /// ```
+    pub fn redact_params(mut self, method: &str, fields: &[&str]) -> Self {
+        self.redactions
+            .entry(method.to_string())
+            .or_default()
+            .extend(fields.iter().map(|field| field.to_string()));
+        self
+    }
+
+    /// Log requests whose handler takes longer than `threshold`.
+    ///
+    /// Only slow requests are logged, which keeps log volume low compared to
+    /// logging every request. Each slow request emits a warning with the
+    /// method, id, duration in milliseconds, and the start of its params.
+    /// Notifications are not logged.
+    pub fn log_slow_requests(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Return the messages used for parse and invalid request errors.
+    ///
+    /// Transport integrations use this to report their own parse errors, such
+    /// as an unreadable HTTP body, with the same wording.
+    pub fn error_messages(&self) -> &ErrorMessages {
+        &self.error_messages
+    }
+
+    /// Register an alias for an existing method.
+    ///
+    /// Requests for `alias` are dispatched to the handler registered for
+    /// `method`, whether it was registered with [`JsonRpc::add`] or with one
+    /// of the contextual variants such as [`JsonRpc::add_with_context`]. An
+    /// alias only resolves while `method` has an exact handler, and it never
+    /// shadows an exact registration with the same name. See [`MatchKind`]
+    /// for the full lookup order.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn add(params: (i32, i32)) -> Result<i32, json_rpc::Error> {
+    ///     Ok(params.0 + params.1)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .add("add", add)
+    ///     .alias("sum", "add");
+    /// ```
+    pub fn alias(mut self, alias: &str, method: &str) -> Self {
+        self.aliases.insert(alias.to_string(), method.to_string());
+        self
+    }
+
+    /// Register a handler for every method that starts with `prefix`.
+    ///
+    /// Prefix handlers are used when no exact method or alias matches. When
+    /// several prefixes match, the longest one wins. This is useful for
+    /// namespaced protocols such as `textDocument/` methods.
+    pub fn add_prefix<F, P, R, Fut>(mut self, prefix: &str, handler: F) -> Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        let boxed = box_handler(handler);
+        self.prefixes.retain(|(existing, _)| existing != prefix);
+        self.prefixes.push((prefix.to_string(), boxed));
+        self
+    }
+
+    /// Register a catch-all handler for methods that nothing else matches.
+    ///
+    /// The fallback receives the requested method name together with the
+    /// deserialized parameters. It runs only after exact methods, aliases, and
+    /// prefix handlers fail to match. Registering a new fallback replaces the
+    /// previous one.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    /// use serde_json::Value;
+    ///
+    /// async fn proxy(method: String, params: Value) -> Result<Value, json_rpc::Error> {
+    ///     Ok(serde_json::json!({ "method": method, "params": params }))
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add_fallback(proxy);
+    /// ```
+    pub fn add_fallback<F, P, R, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(String, P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let boxed: BoxedFallback = Box::new(move |method: String, params: serde_json::Value| {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move {
+                let parsed: P = parse_params(params)?;
+                let result = handler(method, parsed).await?;
+                ResultValue::from_result(&result)
+            })
+        });
+
+        self.fallback = Some(boxed);
+        self
+    }
+
+    /// Finish registration and return a shareable, read-only handle.
+    ///
+    /// The returned [`FrozenJsonRpc`] serves requests like the handler it
+    /// wraps but rejects every attempt to register another method, so a late
+    /// registration is reported instead of racing with requests that are
+    /// already being processed. It can be cloned into every task.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+    ///     Ok(params)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add("echo", echo).freeze();
+    /// assert!(json_rpc.insert("late", echo).is_err());
+    /// ```
+    pub fn freeze(self) -> FrozenJsonRpc {
+        FrozenJsonRpc(Arc::new(self))
+    }
+
+    /// Only dispatch `method` while the runtime feature flag `flag` is enabled.
+    ///
+    /// A gated method behaves as if it was not registered while its flag is
+    /// off: requests for it get a "Method not found" error, or reach a
+    /// matching prefix or fallback handler. Aliases of a gated method follow
+    /// the same flag. Flags start disabled; turn them on and off with
+    /// [`JsonRpc::set_flag`] while the handler is serving requests. Several
+    /// methods can share a flag to roll out a group of methods together.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::{JsonRpc, MatchKind};
+    ///
+    /// async fn search(query: String) -> Result<Vec<String>, json_rpc::Error> {
+    ///     Ok(vec![query])
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .add("search.v2", search)
+    ///     .require_flag("search.v2", "new-search");
+    /// assert_eq!(json_rpc.resolve("search.v2"), None);
+    ///
+    /// json_rpc.set_flag("new-search", true);
+    /// assert_eq!(json_rpc.resolve("search.v2"), Some(MatchKind::Exact));
+    /// ```
+    pub fn require_flag(mut self, method: &str, flag: &str) -> Self {
+        self.gates.insert(method.to_string(), flag.to_string());
+        self
+    }
+
+    /// Enable or disable a runtime feature flag.
+    ///
+    /// Takes `&self`, so flags can be flipped on a shared handler without a
+    /// restart. The change applies to requests dispatched after the call.
+    pub fn set_flag(&self, flag: &str, enabled: bool) {
+        let mut flags = self.flags.write().unwrap_or_else(|e| e.into_inner());
+        flags.insert(flag.to_string(), enabled);
+    }
+
+    /// Return `true` if the runtime feature flag is enabled.
+    pub fn flag_enabled(&self, flag: &str) -> bool {
+        let flags = self.flags.read().unwrap_or_else(|e| e.into_inner());
+        flags.get(flag).copied().unwrap_or(false)
+    }
+
+    /// Check that the handler is configured to serve requests.
+    ///
+    /// Returns an error if no method, prefix, or fallback handler is
+    /// registered, because such a handler answers every request with "Method
+    /// not found". Call this after building the handler and before you start
+    /// reading from your transport to catch misconfiguration early.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// assert!(JsonRpc::new().validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.handlers.is_empty()
+            && self.contextual.is_empty()
+            && self.message_handlers.is_empty()
+            && self.prefixes.is_empty()
+            && self.fallback.is_none()
+        {
+            return Err(Error::protocol("no methods registered"));
+        }
+        Ok(())
+    }
+
+    /// Return the parameter and result type names of each typed method.
+    ///
+    /// The map is keyed by method name, with the names from
+    /// `std::any::type_name` for the handler's parameter and result types.
+    /// This is meant for generating API documentation. Type names are best
+    /// effort: their exact form is not stable across compiler versions.
+    /// Message handlers, prefixes, and the fallback have no fixed types and
+    /// are not listed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn add(params: (i32, i32)) -> Result<i32, json_rpc::Error> {
+    ///     Ok(params.0 + params.1)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new().add("add", add);
+    /// let signatures = json_rpc.method_signatures();
+    /// assert_eq!(signatures["add"], ("(i32, i32)".to_string(), "i32".to_string()));
+    /// ```
+    pub fn method_signatures(&self) -> HashMap<String, (String, String)> {
+        self.signatures.clone()
+    }
+
+    /// Remember the parameter and result type names of `method`.
+    fn record_signature<P, R>(&mut self, method: &str) {
+        self.signatures.insert(
+            method.to_string(),
+            (
+                std::any::type_name::<P>().to_string(),
+                std::any::type_name::<R>().to_string(),
+            ),
+        );
+    }
+
+    /// Report which kind of registration would handle `method`.
+    ///
+    /// Returns `None` when the method would produce a "Method not found"
+    /// error. This lets you check the lookup order described in [`MatchKind`]
+    /// without sending a request.
+    pub fn resolve(&self, method: &str) -> Option<MatchKind> {
+        if self.message_handlers.contains_key(method) {
+            return Some(MatchKind::Exact);
+        }
+        self.lookup(method).map(|(kind, _)| kind)
+    }
+
+    /// Call `callback` with the method name of each unhandled notification.
+    ///
+    /// The specification forbids answering a notification, even with a
+    /// "Method not found" error, so a misspelled notification method goes
+    /// unnoticed by the client. The callback surfaces these mistakes, for
+    /// example in logs or metrics, while still sending no response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// let json_rpc = JsonRpc::new().on_unhandled_notification(|method| {
+    ///     eprintln!("No handler for notification {}", method);
+    /// });
+    /// ```
+    pub fn on_unhandled_notification<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.unhandled_notification_hook = Some(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` with the method name and error of each notification
+    /// whose handler fails.
+    ///
+    /// A notification gets no response, so without this the error a handler
+    /// returns for one is only logged. The callback lets operators observe
+    /// these failures, for example in metrics, while still sending no
+    /// response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// let json_rpc = JsonRpc::new().on_notification_error(|method, error| {
+    ///     eprintln!("Notification {} failed: {}", method, error);
+    /// });
+    /// ```
+    pub fn on_notification_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, &Error) + Send + Sync + 'static,
+    {
+        self.notification_error_hook = Some(Box::new(callback));
+        self
+    }
+
+    /// Keep the most recent unknown notifications for inspection.
+    ///
+    /// Notifications for methods without a handler get no response, so by
+    /// default they only show up in the count from
+    /// [`JsonRpc::unknown_notifications`] and in a warning log. With a
+    /// dead-letter buffer, the last `capacity` of them are kept with their
+    /// params and returned by [`JsonRpc::dead_letters`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let json_rpc = JsonRpc::new().with_dead_letters(100);
+    /// json_rpc.call(r#"{"jsonrpc":"2.0","method":"typo","params":[1]}"#).await;
+    ///
+    /// assert_eq!(json_rpc.unknown_notifications(), 1);
+    /// assert_eq!(json_rpc.dead_letters()[0].method, "typo");
+    /// # });
+    /// ```
+    pub fn with_dead_letters(mut self, capacity: usize) -> Self {
+        self.dead_letters = Some((capacity, Mutex::new(VecDeque::with_capacity(capacity))));
+        self
+    }
+
+    /// Reject requests until the `method` handshake has succeeded.
+    ///
+    /// Protocols such as the Language Server Protocol require an
+    /// `initialize` request before anything else. Until a request for
+    /// `method` gets a successful response, every other request is answered
+    /// with a `-32002` "Server not initialized" error without running its
+    /// handler. Notifications and requests for `exit` are always let through.
+    ///
+    /// The serve loops in the [`io`](crate::io) module track the handshake
+    /// per connection, so every connection served by the same `JsonRpc`
+    /// must initialize on its own. Messages passed to [`JsonRpc::call`]
+    /// outside a serve loop share one state that belongs to this handler.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn initialize(_params: serde_json::Value) -> Result<bool, json_rpc::Error> {
+    ///     Ok(true)
+    /// }
+    ///
+    /// let json_rpc = JsonRpc::new()
+    ///     .add("initialize", initialize)
+    ///     .require_initialize("initialize");
+    /// ```
+    pub fn require_initialize(mut self, method: &str) -> Self {
+        self.initialize = Some((method.to_string(), AtomicBool::new(false)));
+        self
+    }
+
+    /// Return the number of notifications received for unknown methods.
+    pub fn unknown_notifications(&self) -> u64 {
+        self.unknown_notifications.load(Ordering::Relaxed)
+    }
+
+    /// Return the kept unknown notifications, oldest first.
+    ///
+    /// Empty unless enabled with [`JsonRpc::with_dead_letters`].
+    pub fn dead_letters(&self) -> Vec<Notification> {
+        match &self.dead_letters {
+            Some((_, letters)) => letters
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Count an unknown notification, report it to the unhandled
+    /// notification callback, and keep it if dead letters are enabled.
+    fn record_dead_letter(&self, notification: Notification) {
+        tracing::warn!(
+            "Dropping notification for unknown method {}",
+            notification.method
+        );
+        self.unknown_notifications.fetch_add(1, Ordering::Relaxed);
+        if let Some(callback) = &self.unhandled_notification_hook {
+            callback(&notification.method);
+        }
+        if let Some((capacity, letters)) = &self.dead_letters {
+            let mut letters = letters.lock().unwrap_or_else(|e| e.into_inner());
+            if letters.len() == *capacity {
+                letters.pop_front();
+            }
+            if *capacity > 0 {
+                letters.push_back(notification);
+            }
+        }
+    }
+
+    /// Return the success and error counts for each called method.
+    ///
+    /// Counts are kept in memory for the lifetime of the handler and include
+    /// both requests and notifications. They are keyed by the method name
+    /// from the message, so calls routed through an alias, prefix, or fallback
+    /// are counted under the name the client used.
+    pub fn stats(&self) -> HashMap<String, MethodStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Record the outcome of a handler call in the per-method stats.
+    fn record(&self, method: &str, success: bool) {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = stats.entry(method.to_string()).or_default();
+        if success {
+            entry.success += 1;
+        } else {
+            entry.error += 1;
+        }
+    }
+
+    /// Find the handler for a method name following the [`MatchKind`] order.
+    fn lookup(&self, method: &str) -> Option<(MatchKind, Route<'_>)> {
+        if let Some(handler) = self.handlers.get(method)
+            && self.gate_open(method)
+        {
+            return Some((MatchKind::Exact, Route::Method(handler)));
+        }
+
+        if let Some(handler) = self.contextual.get(method)
+            && self.gate_open(method)
+        {
+            return Some((MatchKind::Exact, Route::Contextual(handler)));
+        }
+
+        if let Some(target) = self
+            .aliases
+            .get(method)
+            .filter(|target| self.gate_open(method) && self.gate_open(target))
+        {
+            if let Some(handler) = self.handlers.get(target) {
+                return Some((MatchKind::Alias, Route::Method(handler)));
+            }
+            if let Some(handler) = self.contextual.get(target) {
+                return Some((MatchKind::Alias, Route::Contextual(handler)));
+            }
+        }
+
+        if let Some((_, handler)) = self
+            .prefixes
+            .iter()
+            .filter(|(prefix, _)| method.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+        {
+            return Some((MatchKind::Prefix, Route::Method(handler)));
+        }
+
+        self.fallback
+            .as_ref()
+            .map(|handler| (MatchKind::Fallback, Route::Fallback(handler)))
+    }
+
+    /// Return `true` unless `method` is gated by a disabled feature flag.
+    fn gate_open(&self, method: &str) -> bool {
+        self.gates
+            .get(method)
+            .is_none_or(|flag| self.flag_enabled(flag))
+    }
+
+    /// Run the handler for a method, or return `None` if no handler matches.
+    ///
+    /// `id` is the id of the request being handled, or `None` for a
+    /// notification.
+    async fn invoke(
+        &self,
+        method: &str,
+        id: Option<&RequestId>,
+        params: serde_json::Value,
+    ) -> Option<Result<ResultValue, Error>> {
+        let result = match self.lookup(method)? {
+            (_, Route::Method(handler)) => handler(params).await,
+            (_, Route::Contextual(handler)) => {
+                let context = RequestContext {
+                    method: method.to_string(),
+                    id: id.cloned(),
+                    cancellation: CancellationToken::new(),
+                    notifications: NotificationSink::current(),
+                };
+                let _in_flight =
+                    id.map(|id| InFlight::track(self.in_flight(), id, &context.cancellation));
+                handler(params, context).await
+            }
+            (_, Route::Fallback(handler)) => handler(method.to_string(), params).await,
+        };
+        self.record(method, result.is_ok());
+        Some(result)
+    }
+
+    /// Process a JSON-RPC message and return the response JSON string (if any).
+    ///
+    /// This method processes a JSON-RPC message string and returns the response.
+    /// It handles:
+    ///
+    /// - JSON parsing and validation
+    /// - Message type detection (request, notification, batch, response)
+    /// - Method routing and execution
+    /// - Error handling and response generation
+    ///
+    /// Returns `None` for notifications (which don't require a response),
+    /// including batches that contain only notifications.
+    pub async fn call(&self, json_str: &str) -> Option<String> {
+        self.call_with_metadata(json_str)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Process a JSON-RPC message, delivering handler notifications to `sink`.
+    ///
+    /// This works like [`JsonRpc::call`], and notifications that handlers
+    /// send through their [`NotificationSink`] while the message is processed
+    /// go to `sink`. Transports create the sink with
+    /// [`NotificationSink::channel`] and write what arrives on the receiver
+    /// while the call runs.
+    pub async fn call_with_sink(&self, json_str: &str, sink: NotificationSink) -> Option<String> {
+        sink::CURRENT.scope(sink, self.call(json_str)).await
+    }
+
+    /// Process a JSON-RPC message and return the response with its metadata.
+    ///
+    /// This works like [`JsonRpc::call`] and also returns the metadata that
+    /// the hook registered with [`JsonRpc::on_response`] computed over the
+    /// response. The metadata is empty when no hook is registered. Transport
+    /// integrations use this to attach signatures or other derived values to
+    /// the response they send.
+    pub async fn call_with_metadata(&self, json_str: &str) -> Option<(String, ResponseMetadata)> {
+        let response = self.serialize_output(self.process(json_str).await?)?;
+        let metadata = match &self.response_hook {
+            Some(hook) => hook(response.as_bytes()),
+            None => Vec::new(),
+        };
+        Some((response, metadata))
+    }
+
+    /// Process a JSON-RPC message and return the response with a summary of
+    /// its outcome.
+    ///
+    /// This works like [`JsonRpc::call`] and also reports whether the
+    /// response is an error and with which code, so transports can map
+    /// outcomes to status codes or count them without parsing the response
+    /// again. See [`CallOutcome`] for how batches are summarized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use json_rpc::JsonRpc;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let json_rpc = JsonRpc::new();
+    /// let (response, outcome) = json_rpc
+    ///     .call_with_outcome(r#"{"jsonrpc":"2.0","method":"missing","id":1}"#)
+    ///     .await;
+    ///
+    /// assert!(response.is_some());
+    /// assert!(outcome.is_error);
+    /// assert_eq!(outcome.code, Some(-32601));
+    /// # });
+    /// ```
+    pub async fn call_with_outcome(&self, json_str: &str) -> (Option<String>, CallOutcome) {
+        let Some(output) = self.process(json_str).await else {
+            return (None, CallOutcome::NOTIFICATION);
+        };
+        let outcome = CallOutcome::of(&output);
+        (self.serialize_output(output), outcome)
+    }
+
+    /// Serialize the responses to send, logging serialization failures.
+    fn serialize_output(&self, output: Output) -> Option<String> {
+        match output {
+            Output::Single(response) => match serde_json::to_string(&response) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    tracing::error!("Failed to serialize response: {}", e);
+                    None
+                }
+            },
+            Output::Batch(responses) => match self.serialize_batch(&responses) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    tracing::error!("Failed to serialize batch responses: {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Process a JSON-RPC message and write the response to `writer`.
+    ///
+    /// This is the streaming form of [`JsonRpc::call`] for newline-delimited
+    /// transports such as stdio. The response is written as one line ending in
+    /// `\n`, exactly as `call()` would return it. Batch responses are written
+    /// one element at a time instead of being collected into a single string
+    /// first, so a multi-megabyte batch result does not need a second copy in
+    /// memory. With [`BatchFormat::Ndjson`], each batch response is written
+    /// as its own line. Nothing is written for notifications.
+    ///
+    /// Returns an error only if writing to `writer` fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use json_rpc::JsonRpc;
+    ///
+    /// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+    ///     Ok(params)
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let json_rpc = JsonRpc::new().add("echo", echo);
+    /// let mut stdout = tokio::io::stdout();
+    /// json_rpc
+    ///     .call_to_writer(r#"{"jsonrpc":"2.0","method":"echo","params":"hello","id":1}"#, &mut stdout)
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub async fn call_to_writer<W>(&self, json_str: &str, writer: &mut W) -> std::io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let responses = match self.process(json_str).await {
+            Some(Output::Single(response)) => {
+                let mut bytes = serde_json::to_vec(&response)?;
+                bytes.push(b'\n');
+                writer.write_all(&bytes).await?;
+                return writer.flush().await;
+            }
+            Some(Output::Batch(responses)) => responses,
+            None => return Ok(()),
+        };
+
+        let (open, separator, close): (&[u8], &[u8], &[u8]) = match self.batch_format {
+            BatchFormat::Array => (b"[", b",", b"]\n"),
+            BatchFormat::Ndjson => (b"", b"\n", b"\n"),
+        };
+        writer.write_all(open).await?;
+        for (index, response) in responses.iter().enumerate() {
+            if index > 0 {
+                writer.write_all(separator).await?;
+            }
+            writer.write_all(&serde_json::to_vec(response)?).await?;
+        }
+        writer.write_all(close).await?;
+        writer.flush().await
+    }
+
+    /// Serialize batch responses in the configured [`BatchFormat`].
+    fn serialize_batch(&self, responses: &[BatchEntry]) -> serde_json::Result<String> {
+        match self.batch_format {
+            BatchFormat::Array => serde_json::to_string(responses),
+            BatchFormat::Ndjson => {
+                let lines = responses
+                    .iter()
+                    .map(serde_json::to_string)
+                    .collect::<serde_json::Result<Vec<_>>>()?;
+                Ok(lines.join("\n"))
+            }
+        }
+    }
+
+    /// Parse and dispatch a message, returning the responses to send.
+    async fn process(&self, json_str: &str) -> Option<Output> {
+        let output = self.dispatch(json_str).await?;
+        if !self.spec_check {
+            return Some(output);
+        }
+        Some(match output {
+            Output::Single(response) => Output::Single(checked(response)),
+            Output::Batch(entries) => Output::Batch(
+                entries
+                    .into_iter()
+                    .map(|entry| BatchEntry {
+                        response: checked(entry.response),
+                        echo: entry.echo,
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Parse a message and run its handlers.
+    async fn dispatch(&self, json_str: &str) -> Option<Output> {
+        // Some clients prefix messages with a UTF-8 byte order mark.
+        let json_str = json_str.trim_start_matches('\u{feff}').trim();
+        let value: serde_json::Value = match self.parser.parse(json_str) {
+            Ok(v) => v,
+            Err(_) => {
+                let error = self.error_messages.parse_error();
+                return Some(Output::Single(Response::error(RequestId::Null, error)));
+            }
+        };
+
+        let request_id = value.get("id").and_then(|id_value| match id_value {
+            serde_json::Value::Null => Some(RequestId::Null),
+            serde_json::Value::Number(n) => RequestId::from_number_text(&n.to_string()).ok(),
+            serde_json::Value::String(s) => Some(RequestId::String(s.clone())),
+            _ => None,
+        });
+
+        let raw_ids = raw_ids(json_str, &value);
+        let request_id = match raw_ids.first() {
+            Some(Some(id)) if !value.is_array() => Some(id.clone()),
+            _ => request_id,
+        };
+
+        let idempotency_keys = match self.idempotency {
+            Some(_) => idempotency::keys(&value),
+            None => Vec::new(),
+        };
+        let client_timeouts = client_timeouts(&value);
+        let echoes = match (&self.batch_echo, &value) {
+            (Some(member), serde_json::Value::Array(items)) => items
+                .iter()
+                .map(|item| item.get(member).map(|v| (member.clone(), v.clone())))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let message = match Message::from_json(value) {
+            Ok(mut msg) => {
+                restore_ids(&mut msg, raw_ids);
+                msg
+            }
+            Err(Error::InvalidRequest(_)) => {
+                let error = self.error_messages.invalid_request();
+                let id_to_use = request_id.unwrap_or(RequestId::Null);
+                return Some(Output::Single(Response::error(id_to_use, error)));
+            }
+            Err(_) => {
+                let error = crate::types::Error::internal_error("Internal error");
+                let response = Response::error(request_id.unwrap_or(RequestId::Null), error);
+                return Some(Output::Single(response));
+            }
+        };
+
+        match message {
+            Message::Request(request) => {
+                let key = idempotency_keys.into_iter().next().flatten();
+                let timeout = client_timeouts.into_iter().next().flatten();
+                let response = self.process_request_within(request, key, timeout).await;
+                Some(Output::Single(response))
+            }
+            Message::Notification(notification) => {
+                self.process_notification(notification).await;
+                None
+            }
+            Message::Batch(messages) => {
+                let deadline = self
+                    .batch_timeout
+                    .map(|timeout| tokio::time::Instant::now() + timeout);
+
+                // A request repeating an idempotency key from earlier in the
+                // batch waits for that entry's response instead of running
+                // again.
+                let mut keys = idempotency_keys.into_iter();
+                let mut timeouts = client_timeouts.into_iter();
+                let mut echoes = echoes.into_iter();
+                let stream = self.batch_stream();
+                let entries = join_all(messages.into_iter().map(|message| {
+                    let key = keys.next().flatten();
+                    let timeout = timeouts.next().flatten();
+                    let echo = echoes.next().flatten();
+                    let stream = stream.as_ref();
+                    async move {
+                        let response = self
+                            .process_batch_item(message, key, timeout, deadline)
+                            .await?;
+                        let entry = BatchEntry { response, echo };
+                        match stream {
+                            Some(stream) => {
+                                self.send_batch_entry(stream, entry);
+                                None
+                            }
+                            None => Some(entry),
+                        }
+                    }
+                }))
+                .await;
+                let entries: Vec<_> = entries.into_iter().flatten().collect();
+
+                if entries.is_empty() {
+                    return None;
+                }
+                Some(Output::Batch(entries))
+            }
+            Message::Response(_response) => None,
+        }
+    }
+
+    /// Return where to send batch responses one by one, if they are streamed
+    /// on the connection being served.
+    fn batch_stream(&self) -> Option<mpsc::UnboundedSender<String>> {
+        if !self.stream_batch_responses {
+            return None;
+        }
+        CONNECTION
+            .try_with(|connection| connection.batch_responses.clone())
+            .ok()
+            .flatten()
+    }
+
+    /// Send one streamed batch response, checked like a buffered one.
+    fn send_batch_entry(&self, stream: &mpsc::UnboundedSender<String>, mut entry: BatchEntry) {
+        if self.spec_check {
+            entry.response = checked(entry.response);
+        }
+        match serde_json::to_string(&entry) {
+            Ok(response) => {
+                let _ = stream.send(response);
+            }
+            Err(e) => tracing::error!("Failed to serialize batch response: {}", e),
+        }
+    }
+
+    /// Process one batch entry, returning its response if it needs one.
+    async fn process_batch_item(
+        &self,
+        message: Message,
+        key: Option<String>,
+        timeout: Option<Duration>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Option<Response> {
+        match message {
+            Message::Request(request) => {
+                let id = request.id.clone();
+                let response =
+                    before(deadline, self.process_request_within(request, key, timeout)).await;
+                Some(response.unwrap_or_else(|| Response::error(id, timed_out())))
+            }
+            Message::Notification(notification) => {
+                before(deadline, self.process_notification(notification)).await;
+                None
+            }
+            Message::Response(response) if is_invalid_batch_item(&response) => {
+                let error = self.error_messages.invalid_request();
+                Some(Response::error(RequestId::Null, error))
+            }
+            Message::Response(response) => Some(response),
+            Message::Batch(_) => Some(Response::error(
+                RequestId::Null,
+                self.error_messages.invalid_request(),
+            )),
+        }
+    }
+
+    /// Dispatch a request under the timeout the client sent with it, if any.
+    ///
+    /// The client's timeout covers the whole dispatch, middleware included,
+    /// and applies on top of the server's request timeout.
+    async fn process_request_within(
+        &self,
+        request: Request,
+        key: Option<String>,
+        timeout: Option<Duration>,
+    ) -> Response {
+        let id = request.id.clone();
+        let method = request.method.clone();
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        before(deadline, self.process_request_once(request, key))
+            .await
+            .unwrap_or_else(|| {
+                tracing::warn!("Request for {} passed the client's timeout", method);
+                Response::error(id, timed_out())
+            })
+    }
+
+    /// Dispatch a request through the middleware chain.
+    ///
+    /// The response must carry the request's id. Responses from the
+    /// idempotency cache take the id of the request they answer, so nothing a
+    /// client sends can break this. A middleware or message handler that
+    /// answers with a different id is a bug: debug builds panic on it, release
+    /// builds log an error and send the response as it is.
+    async fn process_request_once(&self, request: Request, key: Option<String>) -> Response {
+        let id = request.id.clone();
+        let next = Next {
+            json_rpc: self,
+            middleware: &self.middleware,
+            key,
+        };
+        let response = next.run(request).await;
+        if response.id != id {
+            tracing::error!(
+                "Response id {} does not match request id {}",
+                response.id,
+                id
+            );
+            debug_assert_eq!(response.id, id, "Response id does not match request id");
+        }
+        response
+    }
+
+    /// Dispatch a request once the initialize handshake allows it.
+    ///
+    /// Without [`JsonRpc::require_initialize`], this is the same as
+    /// `process_request_cached`.
+    pub(crate) async fn process_request_initialized(
+        &self,
+        request: Request,
+        key: Option<String>,
+    ) -> Response {
+        let Some((method, shared)) = &self.initialize else {
+            return self.process_request_cached(request, key).await;
+        };
+        let connection = CONNECTION.try_with(Arc::clone).ok();
+        let initialized = connection.as_ref().map_or(shared, |c| &c.initialized);
+        let is_initialize = request.method == *method;
+        if !is_initialize && request.method != "exit" && !initialized.load(Ordering::Acquire) {
+            tracing::debug!("Rejecting {} before {}", request.method, method);
+            let error = crate::types::Error::new(-32002, "Server not initialized", None);
+            return Response::error(request.id, error);
+        }
+
+        let response = self.process_request_cached(request, key).await;
+        if is_initialize && response.error.is_none() {
+            initialized.store(true, Ordering::Release);
+        }
+        response
+    }
+
+    /// Dispatch a request unless a cached response exists for its key.
+    ///
+    /// Without idempotency enabled, or without a key, this is the same as
+    /// `process_request`.
+    pub(crate) async fn process_request_cached(
+        &self,
+        request: Request,
+        key: Option<String>,
+    ) -> Response {
+        let id = request.id.clone();
+        let (Some(cache), Some(key)) = (&self.idempotency, key) else {
+            return self
+                .process_request_in_time(request)
+                .await
+                .unwrap_or_else(|| Response::error(id, timed_out()));
+        };
+
+        let key = CacheKey::new(key, &request);
+        loop {
+            match cache.lookup(&key) {
+                Lookup::Cached(mut response) => {
+                    tracing::debug!(
+                        "Returning cached response for idempotency key {}",
+                        key.key()
+                    );
+                    // A retry may reuse the key with a new id; answer with its own id.
+                    response.id = id;
+                    return response;
+                }
+                Lookup::Running(running) => {
+                    tracing::debug!(
+                        "Waiting for running request with idempotency key {}",
+                        key.key()
+                    );
+                    if let Some(mut response) = running.wait().await {
+                        response.id = id;
+                        return response;
+                    }
+                }
+                Lookup::First(claim) => {
+                    return match self.process_request_in_time(request).await {
+                        Some(response) => {
+                            claim.finish(response.clone());
+                            response
+                        }
+                        None => Response::error(id, timed_out()),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Dispatch a request under the request timeout.
+    ///
+    /// Returns `None` if the request timed out.
+    async fn process_request_in_time(&self, request: Request) -> Option<Response> {
+        let method = request.method.clone();
+        let response = before(self.request_deadline(), self.process_request(request)).await;
+        if response.is_none() {
+            tracing::warn!("Request for {} timed out", method);
+        }
+        response
+    }
+
+    /// Return `params` with the redacted fields of `method` masked.
+    fn redacted(&self, method: &str, params: &serde_json::Value) -> serde_json::Value {
+        let mut params = params.clone();
+        if let Some(fields) = self.redactions.get(method) {
+            mask(&mut params, fields);
+        }
+        params
+    }
+
+    /// Return the deadline for a request starting now, if a timeout is set.
+    fn request_deadline(&self) -> Option<tokio::time::Instant> {
+        self.request_timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout)
+    }
+
+    /// Dispatch a request to its handler and build the response.
+    async fn process_request(&self, request: Request) -> Response {
+        if let Some(handler) = self
+            .message_handlers
+            .get(&request.method)
+            .filter(|_| self.gate_open(&request.method))
+        {
+            let id = request.id.clone();
+            let method = request.method.clone();
+            let response = handler(Message::Request(request)).await.unwrap_or_else(|| {
+                let error = crate::types::Error::internal_error("No response from message handler");
+                Response::error(id, error)
+            });
+            self.record(&method, response.error.is_none());
+            return response;
+        }
+
+        let params = request.params.unwrap_or(serde_json::Value::Null);
+        let logged_params = self.redacted(&request.method, &params);
+        tracing::debug!(
+            method = %request.method,
+            id = %request.id,
+            params = %logged_params,
+            "Dispatching request"
+        );
+        let slow_log = self
+            .slow_threshold
+            .map(|threshold| (threshold, Instant::now(), summarize(&logged_params)));
+        let result = self
+            .invoke(&request.method, Some(&request.id), params)
+            .await;
+
+        if let Some((threshold, started, params)) = slow_log {
+            let duration = started.elapsed();
+            if duration > threshold {
+                tracing::warn!(
+                    method = %request.method,
+                    id = %request.id,
+                    duration_ms = duration.as_millis() as u64,
+                    params = %params,
+                    "Slow request"
+                );
+            }
+        }
+
+        match result {
+            Some(Ok(ResultValue::Value(result_value))) => {
+                Response::success(request.id, result_value)
+            }
+            Some(Ok(ResultValue::Raw(raw))) => Response::raw_success(request.id, raw),
+            Some(Err(e)) => {
+                let error = match e {
+                    crate::error::Error::InvalidParams(ref source) if self.verbose_errors => {
+                        let data = serde_details(source);
+                        crate::types::Error::new(-32602, "Invalid params", Some(data))
+                    }
+                    crate::error::Error::ParseError(ref source) if self.verbose_errors => {
+                        crate::types::Error::new(-32603, e.to_string(), Some(serde_details(source)))
+                    }
+                    ref e => crate::types::Error::from(e),
+                };
+                Response::error(request.id, error)
+            }
+            None => {
+                let error = match &self.method_not_found {
+                    Some(build) => build(&request.method),
+                    None => crate::types::Error::method_not_found(format!(
+                        "Unknown method: {}",
+                        request.method
+                    )),
+                };
+                Response::error(request.id, error)
+            }
+        }
+    }
+
+    /// Dispatch a notification to its handler, discarding the result.
+    async fn process_notification(&self, notification: Notification) {
+        let method = notification.method.clone();
+        let deadline = self.request_deadline();
+        if before(deadline, self.dispatch_notification(notification))
+            .await
+            .is_none()
+        {
+            tracing::warn!("Notification for {} timed out", method);
+        }
+    }
+
+    /// Run the handler for a notification.
+    async fn dispatch_notification(&self, notification: Notification) {
+        if notification.method == CANCEL_METHOD {
+            self.process_cancel(notification.params);
+            return;
+        }
+
+        if let Some(handler) = self
+            .message_handlers
+            .get(&notification.method)
+            .filter(|_| self.gate_open(&notification.method))
+        {
+            let method = notification.method.clone();
+            let response = handler(Message::Notification(notification)).await;
+            self.record(&method, response.is_none_or(|r| r.error.is_none()));
+            return;
+        }
+
+        if self.lookup(&notification.method).is_none() {
+            self.record_dead_letter(notification);
+            return;
+        }
+
+        let params = notification.params.unwrap_or(serde_json::Value::Null);
+        if let Some(Err(error)) = self.invoke(&notification.method, None, params).await {
+            tracing::warn!("Notification for {} failed: {}", notification.method, error);
+            if let Some(hook) = &self.notification_error_hook {
+                hook(&notification.method, &error);
+            }
+        }
+    }
+
+    /// Handle a `$/cancelRequest` notification.
+    fn process_cancel(&self, params: Option<serde_json::Value>) {
+        let id = params
+            .and_then(|mut params| params.get_mut("id").map(serde_json::Value::take))
+            .and_then(|id| serde_json::from_value::<RequestId>(id).ok());
+        match id {
+            Some(id) if self.cancel(&id) => tracing::debug!("Cancelled request {}", id),
+            Some(id) => tracing::debug!("No cancellable request {} in flight", id),
+            None => tracing::warn!("Ignoring {} without a valid id", CANCEL_METHOD),
+        }
+    }
+}
+
+/// A [`JsonRpc`] whose methods can no longer change, from
+/// [`JsonRpc::freeze`].
+///
+/// It dereferences to the handler, so every `&self` method of [`JsonRpc`]
+/// works on it, including [`JsonRpc::call`]. Registration is rejected:
+/// [`FrozenJsonRpc::insert`] returns an error, and the consuming builder
+/// methods cannot be reached. Runtime state that is meant to change while
+/// serving stays available: feature flags through [`JsonRpc::set_flag`],
+/// cancellation through [`JsonRpc::cancel`], and the statistics and dead
+/// letters the handler records.
+///
+/// Use [`FrozenJsonRpc::shared`] to pass it where an `Arc<JsonRpc>` is
+/// expected, such as axum state.
+#[derive(Clone)]
+pub struct FrozenJsonRpc(Arc<JsonRpc>);
+
+impl FrozenJsonRpc {
+    /// Reject the registration of `method`.
+    ///
+    /// This mirrors [`JsonRpc::insert`] so a late registration compiles and
+    /// fails at runtime with a protocol error, instead of changing the
+    /// methods of a handler that is already serving.
+    pub fn insert<F, P, R, Fut>(&self, method: &str, _handler: F) -> Result<(), Error>
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+        P: serde::de::DeserializeOwned + Send + Sync + 'static,
+        R: Serialize + Send + Sync + 'static,
+    {
+        Err(Error::protocol(format!(
+            "cannot register {} on a frozen handler",
+            method
+        )))
+    }
+
+    /// Return the handler as a shared `Arc<JsonRpc>`.
+    pub fn shared(&self) -> Arc<JsonRpc> {
+        Arc::clone(&self.0)
+    }
+}
+
+impl std::ops::Deref for FrozenJsonRpc {
+    type Target = JsonRpc;
+
+    fn deref(&self) -> &JsonRpc {
+        &self.0
+    }
+}
+
+/// Registration of an in-flight request's cancellation token, removed when
+/// dropped.
+///
+/// Dropping also covers requests abandoned by a timeout. Only the token this
+/// guard registered is removed, so a later request that reused the id keeps
+/// its own.
+struct InFlight {
+    requests: Arc<InFlightRequests>,
+    id: RequestId,
+    token: CancellationToken,
+}
+
+impl InFlight {
+    fn track(requests: Arc<InFlightRequests>, id: &RequestId, token: &CancellationToken) -> Self {
+        requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.clone(), token.clone());
+        Self {
+            requests,
+            id: id.clone(),
+            token: token.clone(),
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        if requests
+            .get(&self.id)
+            .is_some_and(|token| token.same_as(&self.token))
+        {
+            requests.remove(&self.id);
+        }
+    }
+}
+
+/// Wrap a typed handler into a [`BoxedHandler`] that deserializes its params.
+fn box_handler<F, P, R, Fut>(handler: F) -> BoxedHandler
+where
+    F: Fn(P) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+    P: serde::de::DeserializeOwned + Send + Sync + 'static,
+    R: Serialize + Send + Sync + 'static,
+{
+    box_handler_with(handler, ResultValue::from_result::<R>)
+}
+
+/// Wrap a typed handler into a [`BoxedHandler`] that converts its result with
+/// `serialize`.
+fn box_handler_with<F, P, R, Fut, S>(handler: F, serialize: S) -> BoxedHandler
+where
+    F: Fn(P) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<R, Error>> + Send + Sync + 'static,
+    P: serde::de::DeserializeOwned + Send + Sync + 'static,
+    R: Send + Sync + 'static,
+    S: Fn(&R) -> Result<ResultValue, Error> + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+    let serialize = Arc::new(serialize);
+    Box::new(move |params: serde_json::Value| {
+        let handler = Arc::clone(&handler);
+        let serialize = Arc::clone(&serialize);
+        Box::pin(async move {
+            let parsed: P = parse_params(params)?;
+            let result = handler(parsed).await?;
+            serialize(&result)
+        })
+    })
+}
+
+/// Deserialize request params into a handler's parameter type.
+fn parse_params<P: serde::de::DeserializeOwned>(params: serde_json::Value) -> Result<P, Error> {
+    serde_json::from_value(params).map_err(Error::InvalidParams)
+}
+
+/// Read the numeric ids of a message again from the text the client sent.
+///
+/// Returns one entry per message, lined up with the batch like
+/// `client_timeouts`. `serde_json::Value` keeps numbers as `u64`, `i64` or
+/// `f64`, which rewrites ids such as `1e2` or integers beyond `u64`, so these
+/// are read from `json_str` instead. The text is only read again when such an
+/// id is present, and entries are `None` for every other id.
+fn raw_ids(json_str: &str, value: &serde_json::Value) -> Vec<Option<RequestId>> {
+    #[derive(serde::Deserialize)]
+    struct IdOnly<'a> {
+        #[serde(borrow, default)]
+        id: Option<&'a serde_json::value::RawValue>,
+    }
+
+    let rewritten = |item: &serde_json::Value| match item.get("id") {
+        Some(serde_json::Value::Number(n)) => n.as_i64().is_none(),
+        _ => false,
+    };
+    let read = |text: &str| {
+        let id = serde_json::from_str::<IdOnly>(text).ok()?.id?;
+        match serde_json::from_str(id.get()).ok()? {
+            id @ RequestId::Raw(_) => Some(id),
+            _ => None,
+        }
+    };
+
+    match value {
+        serde_json::Value::Array(items) if items.iter().any(rewritten) => {
+            serde_json::from_str::<Vec<&serde_json::value::RawValue>>(json_str)
+                .map(|items| items.iter().map(|item| read(item.get())).collect())
+                .unwrap_or_default()
+        }
+        serde_json::Value::Array(_) => Vec::new(),
+        value if rewritten(value) => vec![read(json_str)],
+        _ => Vec::new(),
+    }
+}
+
+/// Put the ids read by `raw_ids` back into the parsed requests.
+fn restore_ids(message: &mut Message, ids: Vec<Option<RequestId>>) {
+    let restore = |message: &mut Message, id: Option<RequestId>| {
+        if let (Message::Request(request), Some(id)) = (message, id) {
+            request.id = id;
+        }
+    };
+    match message {
+        Message::Batch(messages) => {
+            for (message, id) in messages.iter_mut().zip(ids) {
+                restore(message, id);
+            }
+        }
+        message => {
+            if let Some(id) = ids.into_iter().next() {
+                restore(message, id);
+            }
+        }
+    }
+}
+
+/// Read the client timeouts from a parsed message.
+///
+/// Returns one entry per message, lined up with the batch like
+/// `idempotency::keys`. Values that are not a non-negative integer are ignored.
+fn client_timeouts(value: &serde_json::Value) -> Vec<Option<Duration>> {
+    let timeout = |value: &serde_json::Value| {
+        value
+            .get(TIMEOUT_FIELD)
+            .and_then(serde_json::Value::as_u64)
+            .map(Duration::from_millis)
+    };
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(timeout).collect(),
+        other => vec![timeout(other)],
+    }
+}
+
+/// Run `future` to completion, or until `deadline` passes if one is set.
+///
+/// Returns `None` if the deadline passed first.
+async fn before<F: Future>(deadline: Option<tokio::time::Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) if tokio::time::Instant::now() >= deadline => None,
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// The error sent for a request that did not finish in time.
+fn timed_out() -> crate::types::Error {
+    crate::types::Error::new(-32001, "Request timed out", None)
+}
+
+/// Replace the values of `fields` in every object within `value`.
+fn mask(value: &mut serde_json::Value, fields: &[String]) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                if fields.contains(name) {
+                    *value = serde_json::Value::String("***".to_string());
+                } else {
+                    mask(value, fields);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                mask(item, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Shorten params to a summary suitable for a log line.
+fn summarize(params: &serde_json::Value) -> String {
+    const MAX_CHARS: usize = 100;
+    let text = params.to_string();
+    match text.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// Describe a serde error as a structured `data` object.
+fn serde_details(error: &serde_json::Error) -> serde_json::Value {
+    let category = match error.classify() {
+        serde_json::error::Category::Io => "io",
+        serde_json::error::Category::Syntax => "syntax",
+        serde_json::error::Category::Data => "data",
+        serde_json::error::Category::Eof => "eof",
+    };
+    serde_json::json!({
+        "category": category,
+        "line": error.line(),
+        "column": error.column(),
+        "message": error.to_string(),
+    })
+}
+
+/// Return the first JSON-RPC 2.0 rule that `response` violates, if any.
+fn spec_violation(response: &Response) -> Option<String> {
+    if response.jsonrpc != "2.0" {
+        return Some(format!(
+            "jsonrpc must be \"2.0\", got {:?}",
+            response.jsonrpc
+        ));
+    }
+    if let Err(e) = response.validate() {
+        return Some(e);
+    }
+    if response.has_result() && response.id == RequestId::Null {
+        return Some("Successful response must have a non-null id".to_string());
+    }
+    None
+}
+
+/// Replace a response that violates the specification with an internal error.
+fn checked(response: Response) -> Response {
+    match spec_violation(&response) {
+        None => response,
+        Some(violation) => {
+            tracing::error!(
+                "Response violates JSON-RPC 2.0: {}: {:?}",
+                violation,
+                response
+            );
+            let error = crate::types::Error::internal_error("Internal error");
+            Response::error(response.id, error)
+        }
+    }
+}
+
+/// Check if a batch entry is the placeholder `Message::from_json` creates for
+/// an invalid batch item.
+fn is_invalid_batch_item(response: &Response) -> bool {
+    response.id == RequestId::Null
+        && !response.has_result()
+        && response.error.as_ref().is_some_and(|e| e.code == -32600)
 }
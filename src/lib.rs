@@ -70,6 +70,9 @@
 //! # });
 //! ```
 //!
+//! For transports that deliver whole messages, implement `AsyncJsonRpcIo` and
+//! let `serve_io` drive this loop for you. See the [`io`] module.
+//!
 //! # Struct Parameters
 //!
 //! Handlers can use struct parameters for complex APIs:
@@ -126,7 +129,7 @@
 //! # #[cfg(feature = "axum")]
 //! # {
 //! use json_rpc::{JsonRpc, axum::handler};
-//! use axum::Router;
+//! use axum::{Router, routing::post};
 //! use std::sync::Arc;
 //!
 //! async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
@@ -134,19 +137,49 @@
 //! }
 //!
 //! let json_rpc = JsonRpc::new().add("echo", echo);
-//! let app = Router::new()
-//!     .route("/jsonrpc", handler)
+//! let app: Router = Router::new()
+//!     .route("/jsonrpc", post(handler))
 //!     .with_state(Arc::new(json_rpc));
 //! # }
 //! ```
 
+pub use client::Client;
 pub use error::Error;
-pub use jsonrpc::JsonRpc;
+pub use io::{
+    AsyncJsonRpcIo, serve_duplex, serve_io, serve_reloadable, serve_with_ready, serve_with_shutdown,
+};
+pub use jsonrpc::{
+    BatchFormat, CallOutcome, ErrorMessages, FrozenJsonRpc, JsonRpc, MatchKind, MethodStats,
+    RequestContext, ResponseMetadata,
+};
+pub use parser::{JsonParser, SerdeJsonParser};
+pub use result::{RawJson, ResultBuilder};
 pub use types::{Message, Notification, Request, RequestId, Response};
 
+pub mod cancellation;
+pub mod client;
+pub mod correlator;
 pub mod error;
+pub mod health;
+pub mod idempotency;
+pub mod io;
 pub mod jsonrpc;
+#[doc(hidden)]
+pub mod macros;
+pub mod middleware;
+pub mod parser;
+pub mod record;
+pub mod reload;
+pub mod result;
+pub mod shutdown;
+pub mod sink;
 pub mod types;
 
 #[cfg(feature = "axum")]
 pub mod axum;
+
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(feature = "testing")]
+pub mod test_util;
@@ -0,0 +1,125 @@
+//! Declaring a set of methods with named constants.
+//!
+//! This module provides the `define_methods!` macro. Registering methods by
+//! string name is easy to get wrong: a typo in a client or test only shows up
+//! at runtime as "Method not found". The macro gives every method name a
+//! constant, so callers use the same name the server registers, and checks at
+//! compile time that no name is declared twice.
+
+/// Declare a set of methods and the handlers that serve them.
+///
+/// The macro defines a unit struct with one `&str` constant per method, a
+/// `METHODS` constant listing every name in declaration order, and a
+/// `json_rpc()` function that returns a [`JsonRpc`](crate::JsonRpc) with each
+/// handler registered through [`JsonRpc::add`](crate::JsonRpc::add). Handler
+/// signatures are checked at compile time by `add`'s bounds, and declaring
+/// the same name twice fails to compile.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::{Error, define_methods};
+///
+/// async fn add(params: (i32, i32)) -> Result<i32, Error> {
+///     Ok(params.0 + params.1)
+/// }
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, Error> {
+///     Ok(params)
+/// }
+///
+/// define_methods! {
+///     /// The calculator API.
+///     pub struct Calculator {
+///         ADD = "math.add" => add,
+///         ECHO = "echo" => echo,
+///     }
+/// }
+///
+/// assert_eq!(Calculator::ADD, "math.add");
+/// assert_eq!(Calculator::METHODS, ["math.add", "echo"]);
+/// let json_rpc = Calculator::json_rpc();
+/// ```
+///
+/// Declaring a name twice is a compile error:
+///
+/// ```compile_fail
+/// # use json_rpc::{Error, define_methods};
+/// # async fn echo(params: serde_json::Value) -> Result<serde_json::Value, Error> {
+/// #     Ok(params)
+/// # }
+/// define_methods! {
+///     struct Api {
+///         ECHO = "echo" => echo,
+///         AGAIN = "echo" => echo,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_methods {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($constant:ident = $method:literal => $handler:expr),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $name {
+            $(
+                #[doc = concat!("The `", $method, "` method.")]
+                pub const $constant: &'static str = $method;
+            )*
+
+            /// Every method name, in declaration order.
+            pub const METHODS: &'static [&'static str] = &[$($method),*];
+
+            /// Create a handler with every method registered.
+            pub fn json_rpc() -> $crate::JsonRpc {
+                $crate::JsonRpc::new()
+                    $(.add(Self::$constant, $handler))*
+            }
+        }
+
+        const _: () = assert!(
+            $crate::macros::distinct($name::METHODS),
+            concat!("duplicate method name in ", stringify!($name)),
+        );
+    };
+}
+
+/// Return `true` if no name appears twice in `names`.
+///
+/// Used by `define_methods!` at compile time.
+#[doc(hidden)]
+pub const fn distinct(names: &[&str]) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if same(names[i], names[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Compare two strings in a const context.
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
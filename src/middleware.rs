@@ -0,0 +1,92 @@
+//! Middleware around request dispatch.
+//!
+//! This module provides the `Middleware` trait for code that should run for
+//! every request, such as logging, authentication, or rate limiting, without
+//! wrapping each handler. Register middleware with `JsonRpc::with_middleware`.
+//! Each one receives the request and a `Next` handle: it can answer the
+//! request itself, or call `Next::run` to pass it on to the next middleware
+//! and finally to the method handler.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::JsonRpc;
+use crate::types::{Request, Response};
+
+/// Code that runs around the handling of every request.
+///
+/// Middleware sees requests only, including each request in a batch.
+/// Notifications are dispatched directly.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::middleware::{Middleware, Next};
+/// use json_rpc::{JsonRpc, Request, Response};
+///
+/// struct LogRequests;
+///
+/// impl Middleware for LogRequests {
+///     async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+///         let method = request.method.clone();
+///         let response = next.run(request).await;
+///         println!("{} -> error: {}", method, response.error.is_some());
+///         response
+///     }
+/// }
+///
+/// let json_rpc = JsonRpc::new().with_middleware(LogRequests);
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    /// Handle `request`, either by answering it or by calling
+    /// [`Next::run`].
+    fn handle(&self, request: Request, next: Next<'_>) -> impl Future<Output = Response> + Send;
+}
+
+/// Object-safe form of [`Middleware`] for storage in `JsonRpc`.
+pub(crate) trait BoxedMiddleware: Send + Sync {
+    fn handle_boxed<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>>;
+}
+
+impl<M: Middleware> BoxedMiddleware for M {
+    fn handle_boxed<'a>(
+        &'a self,
+        request: Request,
+        next: Next<'a>,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>> {
+        Box::pin(self.handle(request, next))
+    }
+}
+
+/// The rest of the middleware chain, ending in the method handler.
+pub struct Next<'a> {
+    pub(crate) json_rpc: &'a JsonRpc,
+    pub(crate) middleware: &'a [Box<dyn BoxedMiddleware>],
+    pub(crate) key: Option<String>,
+}
+
+impl Next<'_> {
+    /// Pass `request` on and return the response from further down the
+    /// chain.
+    pub async fn run(self, request: Request) -> Response {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                let next = Next {
+                    json_rpc: self.json_rpc,
+                    middleware: rest,
+                    key: self.key,
+                };
+                first.handle_boxed(request, next).await
+            }
+            None => {
+                self.json_rpc
+                    .process_request_initialized(request, self.key)
+                    .await
+            }
+        }
+    }
+}
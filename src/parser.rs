@@ -0,0 +1,45 @@
+//! Pluggable JSON parsing for incoming messages.
+//!
+//! This module defines the `JsonParser` trait that `JsonRpc` uses to turn an
+//! incoming JSON string into a `serde_json::Value`. The default implementation
+//! uses `serde_json`. You can plug in another parser such as `simd-json`
+//! while keeping `serde_json::Value` as the interchange type.
+
+use crate::error::Error;
+
+/// Parses an incoming JSON string into a `serde_json::Value`.
+///
+/// `JsonRpc` calls `parse` once for every message passed to `call()`. Any error
+/// returned here is reported to the client as a JSON-RPC "Parse error"
+/// (-32700). Implementations must be thread-safe because one `JsonRpc` can
+/// process many messages at the same time.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{Error, JsonParser, JsonRpc};
+///
+/// struct BytesParser;
+///
+/// impl JsonParser for BytesParser {
+///     fn parse(&self, input: &str) -> Result<serde_json::Value, Error> {
+///         Ok(serde_json::from_slice(input.as_bytes())?)
+///     }
+/// }
+///
+/// let json_rpc = JsonRpc::new().with_parser(BytesParser);
+/// ```
+pub trait JsonParser: Send + Sync {
+    /// Parse a JSON string into a value.
+    fn parse(&self, input: &str) -> Result<serde_json::Value, Error>;
+}
+
+/// The default parser, backed by `serde_json::from_str`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerdeJsonParser;
+
+impl JsonParser for SerdeJsonParser {
+    fn parse(&self, input: &str) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::from_str(input)?)
+    }
+}
@@ -0,0 +1,204 @@
+//! Recording and replaying transport sessions for debugging.
+//!
+//! This module provides `Recording`, which wraps any `AsyncJsonRpcIo` and
+//! writes every message it receives and sends to a log, and `Replay`, which
+//! feeds the received messages of such a log back into a server. Together they
+//! reproduce a client's exact sequence of messages against a local build.
+//!
+//! The log holds one JSON object per line:
+//!
+//! ```json
+//! {"timestamp_ms":1760486400000,"direction":"recv","message":"{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":1}"}
+//! ```
+//!
+//! # Example
+//!
+//! ```no_run
+//! use json_rpc::{JsonRpc, io::LineIo, record::Recording, serve_io};
+//!
+//! async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+//!     Ok(params)
+//! }
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let json_rpc = JsonRpc::new().add("echo", echo);
+//! let log = std::fs::File::create("session.jsonl").unwrap();
+//! let io = Recording::new(LineIo::new(tokio::io::stdin(), tokio::io::stdout()), log);
+//! serve_io(io, &json_rpc).await.unwrap();
+//! # });
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::io::AsyncJsonRpcIo;
+
+/// Whether a recorded message was received or sent by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// The message was received from the peer.
+    Recv,
+    /// The message was sent to the peer.
+    Send,
+}
+
+/// One line of a session log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Milliseconds since the Unix epoch when the message passed through.
+    pub timestamp_ms: u64,
+    /// Whether the message was received or sent.
+    pub direction: Direction,
+    /// The message exactly as the transport returned or was given it.
+    pub message: String,
+}
+
+/// A transport that logs every message passing through `inner` to `log`.
+///
+/// Each entry is written and flushed as soon as the message passes, so the
+/// log is complete up to the last message even if the process is killed.
+/// Writing to the log blocks the task briefly, which is fine for the files
+/// and buffers a debugging session uses.
+pub struct Recording<I, W> {
+    inner: I,
+    log: W,
+}
+
+impl<I, W> Recording<I, W>
+where
+    I: AsyncJsonRpcIo + Send,
+    W: Write + Send,
+{
+    /// Record the messages of `inner` to `log`.
+    pub fn new(inner: I, log: W) -> Self {
+        Self { inner, log }
+    }
+
+    /// Return the transport and the log, consuming the recording.
+    pub fn into_inner(self) -> (I, W) {
+        (self.inner, self.log)
+    }
+
+    /// Append one entry to the log.
+    fn write(&mut self, direction: Direction, message: &str) -> Result<(), Error> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_millis() as u64;
+        let entry = Entry {
+            timestamp_ms,
+            direction,
+            message: message.to_string(),
+        };
+        let line = serde_json::to_string(&entry)?;
+        writeln!(self.log, "{}", line)
+            .and_then(|()| self.log.flush())
+            .map_err(|e| Error::protocol(format!("Failed to write session log: {}", e)))
+    }
+}
+
+impl<I, W> AsyncJsonRpcIo for Recording<I, W>
+where
+    I: AsyncJsonRpcIo + Send,
+    W: Write + Send,
+{
+    async fn recv(&mut self) -> Result<Option<String>, Error> {
+        let message = self.inner.recv().await?;
+        if let Some(message) = &message {
+            self.write(Direction::Recv, message)?;
+        }
+        Ok(message)
+    }
+
+    async fn send(&mut self, message: &str) -> Result<(), Error> {
+        self.write(Direction::Send, message)?;
+        self.inner.send(message).await
+    }
+}
+
+/// A transport that plays back the received messages of a session log.
+///
+/// `recv` returns the recorded incoming messages in order and then reports
+/// the end of the transport. Messages the server sends are collected, so
+/// they can be compared with the responses of the recorded session.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::{JsonRpc, record::Replay, serve_io};
+///
+/// # async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+/// #     Ok(params)
+/// # }
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let log = std::io::BufReader::new(std::fs::File::open("session.jsonl").unwrap());
+/// let mut replay = Replay::from_reader(log).unwrap();
+///
+/// serve_io(&mut replay, &json_rpc).await.unwrap();
+/// assert_eq!(replay.sent(), replay.expected());
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    incoming: VecDeque<String>,
+    expected: Vec<String>,
+    sent: Vec<String>,
+}
+
+impl Replay {
+    /// Play back `messages` as the incoming side of a session.
+    pub fn new(messages: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            incoming: messages.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Load a session log written by [`Recording`].
+    ///
+    /// Blank lines are skipped. Returns a parse error for a line that is not
+    /// a log entry.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, Error> {
+        let mut replay = Self::default();
+        for line in reader.lines() {
+            let line =
+                line.map_err(|e| Error::protocol(format!("Failed to read session log: {}", e)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = serde_json::from_str(&line)?;
+            match entry.direction {
+                Direction::Recv => replay.incoming.push_back(entry.message),
+                Direction::Send => replay.expected.push(entry.message),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Return the messages the recorded server sent.
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    /// Return the messages sent during the replay so far.
+    pub fn sent(&self) -> &[String] {
+        &self.sent
+    }
+}
+
+impl AsyncJsonRpcIo for Replay {
+    async fn recv(&mut self) -> Result<Option<String>, Error> {
+        Ok(self.incoming.pop_front())
+    }
+
+    async fn send(&mut self, message: &str) -> Result<(), Error> {
+        self.sent.push(message.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! Swapping the method set of a running server.
+//!
+//! This module provides `Registry`, a shared slot holding the `JsonRpc` that
+//! serves requests. Long-running servers install a new handler with
+//! `Registry::replace`, for example to enable admin methods, and
+//! `serve_reloadable` picks it up from the next message without closing the
+//! connection.
+//!
+//! Messages already being handled finish with the handler they started with.
+//! The new handler starts with its own state, so method statistics, in-flight
+//! cancellation and the idempotency cache do not carry over.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use json_rpc::{JsonRpc, io::LineIo, reload::Registry, serve_reloadable};
+//!
+//! async fn ping(_params: ()) -> Result<&'static str, json_rpc::Error> {
+//!     Ok("pong")
+//! }
+//!
+//! async fn shutdown(_params: ()) -> Result<bool, json_rpc::Error> {
+//!     Ok(true)
+//! }
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let registry = Arc::new(Registry::new(JsonRpc::new().add("ping", ping)));
+//! tokio::spawn({
+//!     let registry = Arc::clone(&registry);
+//!     async move {
+//!         let io = LineIo::new(tokio::io::stdin(), tokio::io::stdout());
+//!         serve_reloadable(io, &registry).await
+//!     }
+//! });
+//!
+//! registry.replace(JsonRpc::new().add("ping", ping).add("admin.shutdown", shutdown));
+//! # });
+//! ```
+
+use std::sync::{Arc, RwLock};
+
+use crate::JsonRpc;
+
+/// A replaceable `JsonRpc` shared between the serve loop and its owner.
+pub struct Registry {
+    current: RwLock<Arc<JsonRpc>>,
+}
+
+impl Registry {
+    /// Create a registry serving `json_rpc`.
+    pub fn new(json_rpc: JsonRpc) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(json_rpc)),
+        }
+    }
+
+    /// Return the handler currently installed.
+    pub fn load(&self) -> Arc<JsonRpc> {
+        let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&current)
+    }
+
+    /// Install `json_rpc` for every message that arrives from now on.
+    pub fn replace(&self, json_rpc: JsonRpc) {
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        *current = Arc::new(json_rpc);
+        tracing::debug!("Installed a new handler");
+    }
+}
@@ -0,0 +1,143 @@
+//! Incremental construction of object results.
+//!
+//! This module provides `ResultBuilder`, a helper for handlers that assemble a
+//! result object from several steps. Instead of building a `serde_json::Value`
+//! by hand, a handler sets named fields as each step finishes and returns the
+//! builder. Register such handlers with `JsonRpc::add_with_builder` to receive
+//! a fresh builder on every call.
+//!
+//! It also provides `RawJson`, a result that is already serialized, such as a
+//! cached response or data proxied from another service.
+
+use std::any::Any;
+use std::hash::{Hash, Hasher};
+
+use serde::{Serialize, Serializer};
+
+use crate::error::Error;
+
+/// Accumulates named fields into a JSON object result.
+///
+/// The builder serializes as a JSON object holding every field that was set.
+/// Setting a field again replaces its value.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::ResultBuilder;
+///
+/// let mut result = ResultBuilder::new();
+/// result.set("user", "alice").unwrap();
+/// result.set("posts", 3).unwrap();
+///
+/// assert_eq!(result.build(), serde_json::json!({"user": "alice", "posts": 3}));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultBuilder {
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ResultBuilder {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a named field of the result.
+    ///
+    /// Returns an error if `value` cannot be serialized to JSON.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Serialize) -> Result<(), Error> {
+        self.fields
+            .insert(name.into(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Return `true` if the field has been set.
+    pub fn contains(&self, name: &str) -> bool {
+        self.fields.contains_key(name)
+    }
+
+    /// Finish the builder and return the result object.
+    pub fn build(self) -> serde_json::Value {
+        serde_json::Value::Object(self.fields)
+    }
+}
+
+impl Serialize for ResultBuilder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.fields.serialize(serializer)
+    }
+}
+
+/// A result that is already serialized JSON text.
+///
+/// The text is checked once when the `RawJson` is created, and serializing it
+/// writes the text as it is, without first deserializing it into a typed
+/// value. A handler that returns `RawJson` gets its text embedded verbatim as
+/// the response `result`, key order and whitespace included. Wrapping
+/// `RawJson` in another type serializes that type into a `serde_json::Value`
+/// as usual.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::RawJson;
+///
+/// let result = RawJson::new(r#"{"cached":true}"#.to_string()).unwrap();
+/// assert_eq!(result.get(), r#"{"cached":true}"#);
+///
+/// assert!(RawJson::new("{not json".to_string()).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawJson(Box<serde_json::value::RawValue>);
+
+impl RawJson {
+    /// Wrap serialized JSON text, returning a parse error if it is not valid
+    /// JSON.
+    pub fn new(json: String) -> Result<Self, Error> {
+        Ok(Self(serde_json::value::RawValue::from_string(json)?))
+    }
+
+    /// Return the JSON text.
+    pub fn get(&self) -> &str {
+        self.0.get()
+    }
+}
+
+impl Serialize for RawJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl PartialEq for RawJson {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for RawJson {}
+
+impl Hash for RawJson {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get().hash(state);
+    }
+}
+
+/// A handler result on its way into a response.
+pub(crate) enum ResultValue {
+    /// A result serialized into a JSON value.
+    Value(serde_json::Value),
+    /// A result that is already serialized, embedded as it is.
+    Raw(RawJson),
+}
+
+impl ResultValue {
+    /// Serialize a handler result, keeping a `RawJson` result as its text.
+    pub(crate) fn from_result<R: Serialize + 'static>(result: &R) -> Result<Self, Error> {
+        if let Some(raw) = (result as &dyn Any).downcast_ref::<RawJson>() {
+            return Ok(Self::Raw(raw.clone()));
+        }
+        Ok(Self::Value(serde_json::to_value(result)?))
+    }
+}
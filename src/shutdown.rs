@@ -0,0 +1,69 @@
+//! Shutdown signalling for serve loops.
+//!
+//! This module provides `ShutdownSignal`, a shared flag that one task sets to
+//! ask serve loops to stop. Synchronous code can poll it with
+//! `is_shutdown_requested`, and async code can await `wait`, for example as
+//! the shutdown future of `serve_with_shutdown`.
+
+use crate::cancellation::CancellationToken;
+
+/// A cloneable handle that requests shutdown.
+///
+/// All clones share the same state. Once signalled, the signal stays set.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::shutdown::ShutdownSignal;
+/// use json_rpc::{JsonRpc, io::LineIo, serve_with_shutdown};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let shutdown = ShutdownSignal::new();
+///
+/// let signal = shutdown.clone();
+/// tokio::spawn(async move {
+///     tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+///     signal.signal();
+/// });
+///
+/// let io = LineIo::new(tokio::io::stdin(), tokio::io::stdout());
+/// serve_with_shutdown(io, &json_rpc, shutdown.wait()).await.unwrap();
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal {
+    token: CancellationToken,
+}
+
+impl ShutdownSignal {
+    /// Create a new signal that is not set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request shutdown, waking every task waiting in
+    /// [`ShutdownSignal::wait`].
+    pub fn signal(&self) {
+        self.token.cancel();
+    }
+
+    /// Return `true` once shutdown has been requested.
+    ///
+    /// This only reads an atomic flag, so it is cheap enough to check on
+    /// every iteration of a synchronous loop.
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Wait until shutdown is requested.
+    ///
+    /// Returns immediately if it already was.
+    pub async fn wait(&self) {
+        self.token.cancelled().await;
+    }
+}
@@ -0,0 +1,80 @@
+//! Notifications sent by handlers while they run.
+//!
+//! This module provides `NotificationSink`, which handlers registered with
+//! `JsonRpc::add_with_sink` use to push notifications, such as progress
+//! updates, to the client before their final result. The serve loops in the
+//! [`io`](crate::io) module write these notifications to the transport as
+//! soon as they are sent. Without a connected transport, such as with a plain
+//! `JsonRpc::call` behind HTTP, notifications are dropped.
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::error::Error;
+use crate::types::Notification;
+
+tokio::task_local! {
+    /// The sink of the call being processed by the current task.
+    pub(crate) static CURRENT: NotificationSink;
+}
+
+/// A handle for sending notifications to the client of the current call.
+///
+/// # Example
+///
+/// ```no_run
+/// use json_rpc::JsonRpc;
+/// use json_rpc::sink::NotificationSink;
+///
+/// async fn index(files: Vec<String>, sink: NotificationSink) -> Result<usize, json_rpc::Error> {
+///     for (done, _file) in files.iter().enumerate() {
+///         sink.send("$/progress", serde_json::json!({ "done": done, "total": files.len() }))?;
+///     }
+///     Ok(files.len())
+/// }
+///
+/// let json_rpc = JsonRpc::new().add_with_sink("index", index);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NotificationSink {
+    sender: Option<mpsc::UnboundedSender<Notification>>,
+}
+
+impl NotificationSink {
+    /// Create a sink and the receiver its notifications are delivered to.
+    ///
+    /// Transports pass the sink to `JsonRpc::call_with_sink` and write out
+    /// what arrives on the receiver.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Notification>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (
+            Self {
+                sender: Some(sender),
+            },
+            receiver,
+        )
+    }
+
+    /// Create a sink that drops every notification.
+    pub fn disconnected() -> Self {
+        Self::default()
+    }
+
+    /// Send a notification with `method` and `params` to the client.
+    ///
+    /// Returns an error only if `params` cannot be serialized. Notifications
+    /// are dropped silently when no transport is connected or it has closed.
+    pub fn send<P: Serialize>(&self, method: &str, params: P) -> Result<(), Error> {
+        let notification = Notification::new(method, Some(serde_json::to_value(params)?));
+        match &self.sender {
+            Some(sender) if sender.send(notification).is_ok() => {}
+            _ => tracing::debug!("Dropping notification {} without a transport", method),
+        }
+        Ok(())
+    }
+
+    /// Return the sink of the call the current task is processing.
+    pub(crate) fn current() -> Self {
+        CURRENT.try_with(Self::clone).unwrap_or_default()
+    }
+}
@@ -0,0 +1,144 @@
+//! In-process harness for testing JSON-RPC handlers.
+//!
+//! This module provides `TestServer`, which serves a `JsonRpc` handler with
+//! `serve_duplex` over an in-memory pipe inside the current tokio runtime.
+//! Tests talk to it with newline-delimited messages exactly as a stdio or
+//! socket client would, without building and spawning a separate binary.
+//! `serve_n` serves a fixed number of messages in place, for tests that write
+//! all their input up front.
+//!
+//! The module is only built with the `testing` feature. Enable it for tests
+//! in `dev-dependencies`:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! json-rpc-rs = { version = "0.3", features = ["testing"] }
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use json_rpc::{JsonRpc, test_util::TestServer};
+//!
+//! async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+//!     Ok(params)
+//! }
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let mut server = TestServer::start(JsonRpc::new().add("echo", echo));
+//!
+//! let response = server
+//!     .request(r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}"#)
+//!     .await
+//!     .unwrap();
+//! assert_eq!(response, r#"{"jsonrpc":"2.0","result":"hi","id":1}"#);
+//!
+//! server.shutdown().await.unwrap();
+//! # });
+//! ```
+
+use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+use tokio::task::JoinHandle;
+
+use crate::JsonRpc;
+use crate::error::Error;
+use crate::io::{AsyncJsonRpcIo, LineIo, serve_duplex, serve_loop};
+
+/// Size of the in-memory pipe buffer in each direction.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// A `JsonRpc` handler served over an in-memory pipe.
+///
+/// Messages are sent one per line, so each message must be a single line of
+/// JSON. Responses are returned without their trailing newline.
+pub struct TestServer {
+    io: LineIo<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>,
+    server: JoinHandle<Result<(), Error>>,
+}
+
+impl TestServer {
+    /// Start serving `json_rpc` on a task of the current tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn start(json_rpc: JsonRpc) -> Self {
+        let (client, server) = tokio::io::duplex(PIPE_CAPACITY);
+        let server = tokio::spawn(async move {
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        Self {
+            io: LineIo::new(reader, writer),
+            server,
+        }
+    }
+
+    /// Send one message without waiting for a response.
+    pub async fn send(&mut self, message: &str) -> Result<(), Error> {
+        self.io.send(message).await
+    }
+
+    /// Receive the next response line.
+    ///
+    /// Returns `Ok(None)` if the server stopped.
+    pub async fn recv(&mut self) -> Result<Option<String>, Error> {
+        let line = self.io.recv().await?;
+        Ok(line.map(|line| line.trim_end().to_string()))
+    }
+
+    /// Send a message and wait for the response line.
+    ///
+    /// Only use this for messages that get a response. For notifications use
+    /// [`TestServer::send`], then check that the next response belongs to a
+    /// later request.
+    pub async fn request(&mut self, message: &str) -> Result<String, Error> {
+        self.send(message).await?;
+        self.recv()
+            .await?
+            .ok_or_else(|| Error::protocol("Server stopped before responding"))
+    }
+
+    /// Close the pipe and wait for the server to finish.
+    pub async fn shutdown(self) -> Result<(), Error> {
+        drop(self.io);
+        self.server
+            .await
+            .map_err(|e| Error::protocol(format!("Server task failed: {}", e)))?
+    }
+}
+
+/// Serve exactly `n` messages from `io`, then return.
+///
+/// This runs the same loop as [`serve_io`](crate::serve_io), so a test can
+/// write its messages to the other end of a pipe, serve them in place
+/// without spawning a task, and then read the responses. Empty messages are
+/// skipped and do not count. Returns early with `Ok(())` if the transport
+/// closes first.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::{JsonRpc, io::LineIo, test_util::serve_n};
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let json_rpc = JsonRpc::new().add("echo", echo);
+/// let input = b"{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"params\":1,\"id\":1}\n";
+/// let mut output = Vec::new();
+///
+/// serve_n(LineIo::new(&input[..], &mut output), &json_rpc, 1).await.unwrap();
+/// assert_eq!(output, b"{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n");
+/// # });
+/// ```
+pub async fn serve_n<I>(io: I, json_rpc: &JsonRpc, n: usize) -> Result<(), Error>
+where
+    I: AsyncJsonRpcIo,
+{
+    serve_loop(io, || json_rpc, std::future::pending(), Some(n)).await
+}
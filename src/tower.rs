@@ -0,0 +1,79 @@
+//! Tower integration for JSON-RPC handlers.
+//!
+//! This module provides `JsonRpcService`, a `tower::Service` that takes a
+//! JSON-RPC message as a string and returns the response string from
+//! `JsonRpc::call()`. Wrap it in tower layers such as timeouts, load
+//! shedding, or tracing, and drive it from any transport. Enable the `tower`
+//! feature in Cargo.toml to use it.
+//!
+//! ```toml
+//! [dependencies]
+//! json-rpc-rs = { version = "0.3", features = ["tower"] }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::JsonRpc;
+
+/// A `tower::Service` that processes JSON-RPC messages with a `JsonRpc`.
+///
+/// The response is `None` for notifications and batches of notifications,
+/// exactly as from [`JsonRpc::call`]. The service is always ready and never
+/// fails: protocol errors are answered with JSON-RPC error responses.
+/// Clones share the same handler.
+///
+/// # Example
+///
+/// ```
+/// use json_rpc::JsonRpc;
+/// use tower_service::Service;
+///
+/// async fn echo(params: serde_json::Value) -> Result<serde_json::Value, json_rpc::Error> {
+///     Ok(params)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut service = JsonRpc::new().add("echo", echo).into_tower_service();
+///
+/// let request = r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}"#;
+/// let response = service.call(request.to_string()).await.unwrap();
+/// assert_eq!(response.as_deref(), Some(r#"{"jsonrpc":"2.0","result":"hi","id":1}"#));
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct JsonRpcService {
+    json_rpc: Arc<JsonRpc>,
+}
+
+impl JsonRpcService {
+    /// Create a service backed by a shared handler.
+    pub fn new(json_rpc: Arc<JsonRpc>) -> Self {
+        Self { json_rpc }
+    }
+}
+
+impl JsonRpc {
+    /// Turn the handler into a [`JsonRpcService`].
+    pub fn into_tower_service(self) -> JsonRpcService {
+        JsonRpcService::new(Arc::new(self))
+    }
+}
+
+impl tower_service::Service<String> for JsonRpcService {
+    type Response = Option<String>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Option<String>, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: String) -> Self::Future {
+        let json_rpc = Arc::clone(&self.json_rpc);
+        Box::pin(async move { Ok(json_rpc.call(&request).await) })
+    }
+}
@@ -3,14 +3,18 @@
 //! This module defines JSON-RPC 2.0 message types as specified in:
 //! https://www.jsonrpc.org/specification
 
+use std::borrow::Cow;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tracing::debug;
 
 use crate::error::Error as InternalError;
+use crate::result::RawJson;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Request {
     pub jsonrpc: String,
     pub id: RequestId,
@@ -34,12 +38,20 @@ impl Request {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A JSON-RPC response.
+///
+/// A successful response carries its result in `result`, or in `raw_result`
+/// when the result is already serialized, such as a handler that returned
+/// [`RawJson`]. Two responses are equal when they are sent as the same JSON,
+/// so a `raw_result` equals a `result` that serializes to the same text.
+#[derive(Debug, Clone, Deserialize)]
 pub struct Response {
     pub jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// A result that is already serialized, written as it is in place of
+    /// `result`. At most one of `result` and `raw_result` is set.
+    #[serde(skip)]
+    pub raw_result: Option<RawJson>,
     pub error: Option<Error>,
     pub id: RequestId,
 }
@@ -51,6 +63,20 @@ impl Response {
             id,
             result: Some(result),
             error: None,
+            raw_result: None,
+        }
+    }
+
+    /// Build a success response whose result is embedded as the raw text.
+    ///
+    /// The text is kept in `raw_result`, and `result` stays `None`.
+    pub fn raw_success(id: RequestId, result: RawJson) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: None,
+            raw_result: Some(result),
         }
     }
 
@@ -60,19 +86,78 @@ impl Response {
             id,
             result: None,
             error: Some(error),
+            raw_result: None,
         }
     }
 
+    /// Return `true` if the response has a result, raw or not.
+    pub fn has_result(&self) -> bool {
+        self.result.is_some() || self.raw_result.is_some()
+    }
+
     pub fn validate(&self) -> Result<(), String> {
-        match (&self.result, &self.error) {
-            (Some(_), Some(_)) => Err("Response cannot have both result and error".to_string()),
-            (None, None) => Err("Response must have either result or error".to_string()),
+        if self.result.is_some() && self.raw_result.is_some() {
+            return Err("Response cannot have both result and raw_result".to_string());
+        }
+        match (self.has_result(), &self.error) {
+            (true, Some(_)) => Err("Response cannot have both result and error".to_string()),
+            (false, None) => Err("Response must have either result or error".to_string()),
             _ => Ok(()),
         }
     }
+
+    /// Return the result as the JSON text it is sent as.
+    fn result_text(&self) -> Option<Cow<'_, str>> {
+        match (&self.raw_result, &self.result) {
+            (Some(raw), _) => Some(Cow::Borrowed(raw.get())),
+            (None, Some(result)) => Some(Cow::Owned(result.to_string())),
+            (None, None) => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl PartialEq for Response {
+    fn eq(&self, other: &Self) -> bool {
+        self.jsonrpc == other.jsonrpc
+            && self.id == other.id
+            && self.error == other.error
+            && self.result_text() == other.result_text()
+    }
+}
+
+impl Eq for Response {}
+
+impl Hash for Response {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.jsonrpc.hash(state);
+        self.result_text().hash(state);
+        self.error.hash(state);
+        self.id.hash(state);
+    }
+}
+
+impl Serialize for Response {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = 2 + usize::from(self.has_result()) + usize::from(self.error.is_some());
+        let mut state = serializer.serialize_struct("Response", len)?;
+        state.serialize_field("jsonrpc", &self.jsonrpc)?;
+        if let Some(raw) = &self.raw_result {
+            state.serialize_field("result", raw)?;
+        } else if let Some(result) = &self.result {
+            state.serialize_field("result", result)?;
+        } else {
+            state.skip_field("result")?;
+        }
+        match &self.error {
+            Some(error) => state.serialize_field("error", error)?,
+            None => state.skip_field("error")?,
+        }
+        state.serialize_field("id", &self.id)?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Notification {
     pub jsonrpc: String,
     pub method: String,
@@ -90,7 +175,7 @@ impl Notification {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Error {
     pub code: i32,
     pub message: String,
@@ -134,19 +219,52 @@ impl fmt::Display for Error {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum RequestId {
     Null,
-    Number(u64),
+    Number(i64),
+    /// A numeric id that an `i64` cannot echo exactly, such as `1.0`, `1e2`
+    /// or a large unsigned integer, kept as the text that was sent so it is
+    /// echoed back byte for byte.
+    Raw(RawJson),
     String(String),
 }
 
+impl RequestId {
+    /// Build a numeric id from its JSON text.
+    pub(crate) fn from_number_text(text: &str) -> Result<Self, InternalError> {
+        match text.parse::<i64>() {
+            Ok(n) if n.to_string() == text => Ok(RequestId::Number(n)),
+            _ => Ok(RequestId::Raw(RawJson::new(text.to_string())?)),
+        }
+    }
+}
+
+// Read through `RawValue` so numeric ids keep the text that was sent.
+impl<'de> Deserialize<'de> for RequestId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let raw = Box::<serde_json::value::RawValue>::deserialize(deserializer)?;
+        let text = raw.get();
+        match text.as_bytes().first() {
+            Some(b'"') => serde_json::from_str(text)
+                .map(RequestId::String)
+                .map_err(D::Error::custom),
+            Some(b'-' | b'0'..=b'9') => RequestId::from_number_text(text).map_err(D::Error::custom),
+            _ if text == "null" => Ok(RequestId::Null),
+            _ => Err(D::Error::custom(format!("invalid request id: {}", text))),
+        }
+    }
+}
+
 impl fmt::Display for RequestId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             RequestId::Null => write!(f, "null"),
             RequestId::Number(n) => write!(f, "{}", n),
+            RequestId::Raw(n) => write!(f, "{}", n.get()),
             RequestId::String(s) => write!(f, "{}", s),
         }
     }
@@ -162,7 +280,7 @@ pub enum Message {
 
 impl Message {
     pub fn from_json(value: serde_json::Value) -> Result<Self, InternalError> {
-        debug!("Parsing JSON value: {:?}", value);
+        debug!("Parsing JSON-RPC message");
         if let Some(arr) = value.as_array() {
             return Self::parse_batch(arr);
         }
@@ -192,9 +310,19 @@ impl Message {
     }
 
     fn parse_single(value: serde_json::Value) -> Result<Self, InternalError> {
-        let has_id = value.get("id").is_some();
+        let id = value.get("id");
+
+        // Only strings, numbers, and null are valid ids.
+        if matches!(
+            id,
+            Some(serde_json::Value::Bool(_))
+                | Some(serde_json::Value::Array(_))
+                | Some(serde_json::Value::Object(_))
+        ) {
+            return Err(InternalError::invalid_request("Invalid Request"));
+        }
 
-        if has_id {
+        if id.is_some() {
             if value.get("error").is_some() {
                 let resp: Response = serde_json::from_value(value)
                     .map_err(|_| InternalError::invalid_request("Invalid Request"))?;
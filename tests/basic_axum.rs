@@ -270,7 +270,7 @@ mod tests {
         });
 
         let response = send_request(request).await.trim_end().to_string();
-        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Protocol error: invalid type: null, expected a string"},"id":1}"#;
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: null, expected a string"},"id":1}"#;
         assert_eq!(response, expected_response);
     }
 
@@ -284,7 +284,7 @@ mod tests {
         });
 
         let response = send_request(request).await.trim_end().to_string();
-        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Protocol error: invalid type: integer `123`, expected a string"},"id":1}"#;
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: integer `123`, expected a string"},"id":1}"#;
         assert_eq!(response, expected_response);
     }
 
@@ -298,7 +298,7 @@ mod tests {
         });
 
         let response = send_request(request).await.trim_end().to_string();
-        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Protocol error: invalid type: map, expected a string"},"id":1}"#;
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: map, expected a string"},"id":1}"#;
         assert_eq!(response, expected_response);
     }
 
@@ -312,7 +312,7 @@ mod tests {
         });
 
         let response = send_request(request).await.trim_end().to_string();
-        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Protocol error: invalid type: sequence, expected a string"},"id":1}"#;
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: sequence, expected a string"},"id":1}"#;
         assert_eq!(response, expected_response);
     }
 
@@ -378,6 +378,31 @@ mod tests {
         assert_eq!(response, expected_response);
     }
 
+    #[tokio::test]
+    async fn batch_mixed_requests_and_notifications() {
+        let request = json!([
+            {"jsonrpc": "2.0", "method": "hello", "params": "world", "id": 1},
+            {"jsonrpc": "2.0", "method": "hello", "params": "world"},
+            {"foo": "boo"},
+            {"jsonrpc": "2.0", "method": "hello", "params": "earth", "id": 2}
+        ]);
+
+        let response = send_request(request).await.trim_end().to_string();
+        let expected_response = r#"[{"jsonrpc":"2.0","result":"Hello, world!","id":1},{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null},{"jsonrpc":"2.0","error":{"code":-32000,"message":"text must be 'world'"},"id":2}]"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn batch_all_notifications_no_response() {
+        let request = json!([
+            {"jsonrpc": "2.0", "method": "hello", "params": "world"},
+            {"jsonrpc": "2.0", "method": "hello", "params": "earth"}
+        ]);
+
+        let response = send_request(request).await.trim_end().to_string();
+        assert_eq!(response, "");
+    }
+
     #[tokio::test]
     async fn notification_valid_no_response() {
         let request = json!({
@@ -0,0 +1,207 @@
+//! Integration tests for the JSON-RPC client.
+//!
+//! This test suite serves a `JsonRpc` handler with `serve_duplex` on one end of
+//! an in-memory `tokio::io::duplex` pipe and calls it with a `Client` on the
+//! other end.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test client
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use json_rpc::client::ErrorResponse;
+    use json_rpc::io::LineIo;
+    use json_rpc::{Client, Error, JsonRpc, RequestContext, RequestId, serve_duplex};
+    use std::time::{Duration, Instant};
+
+    use serde::{Deserialize, Serialize};
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    async fn translate(params: (Point, i32)) -> Result<Point, Error> {
+        let (point, by) = params;
+        Ok(Point {
+            x: point.x + by,
+            y: point.y + by,
+        })
+    }
+
+    async fn divide(params: (i32, i32)) -> Result<i32, Error> {
+        if params.1 == 0 {
+            return Err(Error::rpc(-32000, "Division by zero"));
+        }
+        Ok(params.0 / params.1)
+    }
+
+    async fn request_id(_params: (), context: RequestContext) -> Result<RequestId, Error> {
+        Ok(context.id.unwrap_or(RequestId::Null))
+    }
+
+    async fn sleep(millis: u64) -> Result<u64, Error> {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok(millis)
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Conflict {
+        existing: String,
+        suggestions: Vec<String>,
+    }
+
+    async fn register(name: String) -> Result<u64, Error> {
+        if name == "alice" {
+            let conflict = Conflict {
+                existing: name.clone(),
+                suggestions: vec![format!("{}1", name), format!("{}_", name)],
+            };
+            return Err(Error::rpc_with_data(
+                -32010,
+                "Name taken",
+                serde_json::to_value(conflict)?,
+            ));
+        }
+        Ok(7)
+    }
+
+    type DuplexClient = Client<LineIo<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>>;
+
+    /// Start a server task on one end of a pipe and return a client for the
+    /// other end.
+    fn connect() -> DuplexClient {
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            let json_rpc = JsonRpc::new()
+                .add("translate", translate)
+                .add("divide", divide)
+                .add_with_context("request_id", request_id)
+                .add("sleep", sleep)
+                .add("register", register)
+                .with_request_timeout(Duration::from_secs(5));
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        Client::new(LineIo::new(reader, writer))
+    }
+
+    #[tokio::test]
+    async fn call_deserializes_typed_result() {
+        let mut client = connect();
+
+        let point: Point = client
+            .call("translate", (Point { x: 1, y: 2 }, 10))
+            .await
+            .unwrap();
+        assert_eq!(point, Point { x: 11, y: 12 });
+
+        let quotient: i32 = client.call("divide", (10, 2)).await.unwrap();
+        assert_eq!(quotient, 5);
+    }
+
+    #[tokio::test]
+    async fn call_surfaces_error_objects() {
+        let mut client = connect();
+
+        let error = client.call::<_, i32>("divide", (1, 0)).await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::RpcError { code: -32000, ref message, .. } if message == "Division by zero"
+        ));
+
+        let error = client.call::<_, i32>("missing", ()).await.unwrap_err();
+        assert!(matches!(error, Error::RpcError { code: -32601, .. }));
+    }
+
+    #[tokio::test]
+    async fn notify_gets_no_response() {
+        let mut client = connect();
+
+        client.notify("divide", (1, 0)).await.unwrap();
+        let quotient: i32 = client.call("divide", (9, 3)).await.unwrap();
+        assert_eq!(quotient, 3);
+    }
+
+    #[tokio::test]
+    async fn id_generator_assigns_request_ids() {
+        let mut state: u64 = 0x9e37_79b9;
+        let mut client = connect().with_id_generator(move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            RequestId::String(format!(
+                "{:08x}-{:04x}-4{:03x}",
+                state >> 32,
+                (state >> 16) & 0xffff,
+                state & 0xfff
+            ))
+        });
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let id: RequestId = client.call("request_id", ()).await.unwrap();
+            assert!(matches!(id, RequestId::String(_)));
+            assert!(!ids.contains(&id));
+            ids.push(id);
+        }
+    }
+
+    #[tokio::test]
+    async fn client_timeout_wins_over_server_timeout() {
+        let mut client = connect().with_default_timeout(Duration::from_millis(50));
+
+        let started = Instant::now();
+        let error = client.call::<_, u64>("sleep", 3000).await.unwrap_err();
+        assert!(matches!(error, Error::RpcError { code: -32001, .. }));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let millis: u64 = client
+            .call_with_timeout("sleep", 0, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(millis, 0);
+    }
+
+    #[tokio::test]
+    async fn error_data_round_trips_to_the_client() {
+        let mut client = connect();
+
+        let error = client
+            .call::<_, u64>("register", "alice")
+            .await
+            .unwrap_err();
+        let Error::RpcError { code, data, .. } = error else {
+            panic!("expected an error response, got {:?}", error);
+        };
+        assert_eq!(code, -32010);
+        assert_eq!(data.unwrap()["existing"], "alice");
+
+        let response = client
+            .call_typed_error::<_, u64, Conflict>("register", "alice")
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            Err(ErrorResponse {
+                code: -32010,
+                message: "Name taken".to_string(),
+                data: Some(Conflict {
+                    existing: "alice".to_string(),
+                    suggestions: vec!["alice1".to_string(), "alice_".to_string()],
+                }),
+            })
+        );
+
+        let response = client
+            .call_typed_error::<_, u64, Conflict>("register", "bob")
+            .await
+            .unwrap();
+        assert_eq!(response, Ok(7));
+    }
+}
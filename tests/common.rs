@@ -90,7 +90,7 @@ pub fn get_example_path(name: &str) -> Result<PathBuf> {
     let profile = get_profile();
     let binary_path = target_dir.join(profile).join("examples").join(name);
 
-    if matches!(name, "basic_axum|echo_axum") {
+    if matches!(name, "basic_axum" | "echo_axum") {
         ensure_axum_examples_built()?;
     } else {
         ensure_examples_built()?;
@@ -138,10 +138,10 @@ fn get_example_names() -> Result<Vec<String>> {
         let entry = entry?;
         let path = entry.path();
 
-        if path.extension().map_or(false, |ext| ext == "rs") {
-            path.file_stem()
-                .and_then(|stem| stem.to_str())
-                .map(|name| example_names.push(name.to_string()));
+        if path.extension().is_some_and(|ext| ext == "rs")
+            && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+        {
+            example_names.push(name.to_string());
         }
     }
 
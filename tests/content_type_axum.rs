@@ -0,0 +1,81 @@
+//! Integration tests for the content type check of the strict axum handler.
+//!
+//! This test suite serves a `JsonRpc` with `strict_handler` from an in-process
+//! axum server and checks which `Content-Type` headers it accepts.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test content_type_axum --features axum
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Router;
+    use axum::routing::post;
+    use json_rpc::axum::strict_handler;
+    use json_rpc::{Error, JsonRpc};
+    use serde_json::Value;
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    /// Start an axum server with the strict handler on a random local port.
+    async fn start_server() -> String {
+        let app = Router::new()
+            .route("/jsonrpc", post(strict_handler))
+            .with_state(Arc::new(JsonRpc::new().add("echo", echo)));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}/jsonrpc", addr)
+    }
+
+    async fn post_with(url: &str, content_type: Option<&str>) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .post(url)
+            .body(r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}"#);
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_content_types_are_processed() {
+        let url = start_server().await;
+
+        for content_type in ["application/json", "Application/JSON; charset=utf-8"] {
+            let response = post_with(&url, Some(content_type)).await;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            assert_eq!(
+                response.text().await.unwrap(),
+                r#"{"jsonrpc":"2.0","result":"hi","id":1}"#
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn other_content_types_are_rejected() {
+        let url = start_server().await;
+
+        for content_type in [Some("text/plain"), Some("application/jsonp"), None] {
+            let response = post_with(&url, content_type).await;
+            assert_eq!(
+                response.status(),
+                reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+            );
+            assert_eq!(
+                response.text().await.unwrap(),
+                r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Integration tests for the request/response `Correlator`.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test correlator
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use json_rpc::correlator::Correlator;
+    use json_rpc::{RequestId, Response};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn out_of_order_responses_reach_their_requests() {
+        let correlator = Correlator::new();
+        let (first_id, first) = correlator.register();
+        let (second_id, second) = correlator.register();
+        assert_ne!(first_id, second_id);
+        assert_eq!(correlator.pending(), 2);
+
+        assert!(correlator.complete(Response::success(second_id.clone(), json!("second"))));
+        assert!(correlator.complete(Response::success(first_id.clone(), json!("first"))));
+
+        let first = first.await.unwrap();
+        let second = second.await.unwrap();
+        assert_eq!((first.id, first.result), (first_id, Some(json!("first"))));
+        assert_eq!(
+            (second.id, second.result),
+            (second_id, Some(json!("second")))
+        );
+        assert_eq!(correlator.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn unmatched_responses_are_dropped() {
+        let correlator = Correlator::new();
+        let (forgotten_id, _forgotten) = correlator.register();
+        let (id, receiver) = correlator.register();
+        assert!(correlator.forget(&forgotten_id));
+
+        assert!(!correlator.complete(Response::success(forgotten_id, json!("late"))));
+        assert!(!correlator.complete(Response::success(RequestId::Number(99), json!("spurious"))));
+        assert_eq!(correlator.pending(), 1);
+
+        assert!(correlator.complete(Response::success(id, json!("ok"))));
+        assert_eq!(receiver.await.unwrap().result, Some(json!("ok")));
+    }
+}
@@ -0,0 +1,71 @@
+//! Integration tests for JSON-RPC over HTTP GET with the axum `get_handler`.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test get_axum --features axum
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Router;
+    use axum::routing::post;
+    use json_rpc::axum::{get_handler, handler};
+    use json_rpc::{Error, JsonRpc};
+    use serde_json::Value;
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    /// Start an axum server that accepts POST and GET on a random local port.
+    async fn start_server() -> String {
+        let app = Router::new()
+            .route("/jsonrpc", post(handler).get(get_handler))
+            .with_state(Arc::new(JsonRpc::new().add("echo", echo)));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}/jsonrpc", addr)
+    }
+
+    #[tokio::test]
+    async fn get_with_encoded_request_is_processed() {
+        let url = start_server().await;
+
+        let url = reqwest::Url::parse_with_params(
+            &url,
+            [(
+                "request",
+                r#"{"jsonrpc":"2.0","method":"echo","params":["a b&c"],"id":1}"#,
+            )],
+        )
+        .unwrap();
+        let response = reqwest::get(url).await.unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.text().await.unwrap(),
+            r#"{"jsonrpc":"2.0","result":["a b&c"],"id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn get_without_request_parameter_is_rejected() {
+        let url = start_server().await;
+
+        let response = reqwest::get(&url).await.unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.text().await.unwrap(),
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#
+        );
+    }
+}
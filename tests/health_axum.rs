@@ -0,0 +1,79 @@
+//! Integration tests for the axum health endpoints.
+//!
+//! This test suite serves the `liveness` and `readiness` handlers from an
+//! in-process axum server and checks the status codes for each readiness
+//! stage.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test health_axum --features axum
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::routing::get;
+    use json_rpc::axum::{liveness, readiness};
+    use json_rpc::health::ReadinessState;
+    use reqwest::StatusCode;
+
+    /// Start an axum server with health endpoints on a random local port.
+    async fn start_server(state: ReadinessState) -> String {
+        let app = Router::new()
+            .route("/livez", get(liveness))
+            .route("/readyz", get(readiness))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}", addr)
+    }
+
+    async fn status(url: &str) -> StatusCode {
+        reqwest::get(url).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn starting_server_is_live_but_not_ready() {
+        let state = ReadinessState::new();
+        let base = start_server(state).await;
+
+        assert_eq!(status(&format!("{}/livez", base)).await, StatusCode::OK);
+        assert_eq!(
+            status(&format!("{}/readyz", base)).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn ready_server_reports_ready() {
+        let state = ReadinessState::new();
+        let base = start_server(state.clone()).await;
+
+        state.set_ready();
+
+        assert_eq!(status(&format!("{}/livez", base)).await, StatusCode::OK);
+        assert_eq!(status(&format!("{}/readyz", base)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn draining_server_is_live_but_not_ready() {
+        let state = ReadinessState::new();
+        let base = start_server(state.clone()).await;
+
+        state.set_ready();
+        assert_eq!(status(&format!("{}/readyz", base)).await, StatusCode::OK);
+
+        state.set_draining();
+        assert_eq!(
+            status(&format!("{}/readyz", base)).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&format!("{}/livez", base)).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,343 @@
+//! In-process integration tests for JSON-RPC 2.0 error handling.
+//!
+//! This test suite serves the methods of the basic examples with
+//! `test_util::TestServer`, which runs the handler over an in-memory pipe in
+//! the test's own runtime instead of spawning an example binary. It tests all
+//! error codes defined in the JSON-RPC 2.0 specification, batches, and
+//! notifications.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test in_process
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use json_rpc::test_util::TestServer;
+    use json_rpc::{Error, JsonRpc};
+    use serde_json::{Value, json};
+
+    /// Greet the user with their name.
+    async fn hello(params: String) -> Result<String, Error> {
+        if params == "world" {
+            Ok(format!("Hello, {}!", params))
+        } else {
+            Err(Error::rpc(-32000, "text must be 'world'"))
+        }
+    }
+
+    /// Subtract two numbers.
+    async fn subtract(params: (i32, i32)) -> Result<i32, Error> {
+        Ok(params.0 - params.1)
+    }
+
+    /// Sum an array of numbers.
+    async fn sum(params: Vec<i32>) -> Result<i32, Error> {
+        Ok(params.into_iter().sum())
+    }
+
+    /// Demonstrates internal errors.
+    async fn fail_internally(_params: Value) -> Result<String, Error> {
+        Err(Error::protocol("Internal error occurred"))
+    }
+
+    /// A request sent after each message to mark the end of its output.
+    const PROBE: &str = r#"{"jsonrpc":"2.0","method":"probe","id":"probe"}"#;
+
+    /// Send one message and return everything the server wrote for it.
+    ///
+    /// Like piping the message into the stdio example, this returns an empty
+    /// string for messages that get no response.
+    async fn send_request(request: &str) -> String {
+        let mut server = TestServer::start(
+            JsonRpc::new()
+                .add("hello", hello)
+                .add("subtract", subtract)
+                .add("sum", sum)
+                .add("internal_error", fail_internally),
+        );
+
+        server.send(request).await.unwrap();
+        server.send(PROBE).await.unwrap();
+
+        let mut output = String::new();
+        loop {
+            let line = server.recv().await.unwrap().expect("server stopped");
+            if line.ends_with(r#""id":"probe"}"#) {
+                break;
+            }
+            output.push_str(&line);
+            output.push('\n');
+        }
+
+        server.shutdown().await.unwrap();
+        output
+    }
+
+    #[tokio::test]
+    async fn parse_error_invalid_json() {
+        let request = r#"{"jsonrpc":"2.0","method":"hello","params":"world""#;
+        let response = send_request(request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn parse_error_malformed_json() {
+        let request = r#"invalid json"#;
+        let response = send_request(request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_missing_jsonrpc() {
+        let request = json!({
+            "method": "hello",
+            "params": "world",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_missing_method() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "params": "world",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_invalid_jsonrpc_value() {
+        let request = json!({
+            "jsonrpc": "1.0",
+            "method": "hello",
+            "params": "world",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":1}"#;
+
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_method_wrong_type() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": 123,
+            "params": "world",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":1}"#;
+
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_request_empty_object() {
+        let request = json!({}).to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn method_not_found_nonexistent_method() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "nonexistent",
+            "params": "test",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Unknown method: nonexistent"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_missing_for_hello() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: null, expected a string"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_wrong_type() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": 123,
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: integer `123`, expected a string"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_object_instead_of_string() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": {"text": "world"},
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: map, expected a string"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_multiple_params() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": ["world", "extra"],
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"invalid type: sequence, expected a string"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn internal_error() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "internal_error",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Protocol error: Internal error occurred"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn server_error_custom() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": "earth",
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"text must be 'world'"},"id":1}"#;
+
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn batch_invalid_request_empty_array() {
+        let request = json!([]).to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn batch_invalid_individual_request() {
+        let request = json!([1]).to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response =
+            r#"[{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}]"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn batch_mixed_valid_invalid_requests() {
+        let request = json!([
+            {"jsonrpc": "2.0", "method": "hello", "params": "world", "id": 1},
+            {"foo": "boo"},
+            {"jsonrpc": "2.0", "method": "hello", "params": "earth", "id": 2}
+        ])
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"[{"jsonrpc":"2.0","result":"Hello, world!","id":1},{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null},{"jsonrpc":"2.0","error":{"code":-32000,"message":"text must be 'world'"},"id":2}]"#;
+        assert_eq!(response, expected_response);
+    }
+
+    #[tokio::test]
+    async fn notification_valid_no_response() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": "world"
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        assert_eq!(response, "");
+    }
+
+    #[tokio::test]
+    async fn notification_with_invalid_params_no_response() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "hello",
+            "params": 123
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        assert_eq!(response, "");
+    }
+
+    #[tokio::test]
+    async fn notification_nonexistent_method_no_response() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "nonexistent",
+            "params": "test"
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        assert_eq!(response, "");
+    }
+
+    #[tokio::test]
+    async fn method_not_found_with_params() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "unknown",
+            "params": {"key": "value"},
+            "id": 1
+        })
+        .to_string();
+        let response = send_request(&request).await.trim_end().to_string();
+        let expected_response = r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Unknown method: unknown"},"id":1}"#;
+        assert_eq!(response, expected_response);
+    }
+}
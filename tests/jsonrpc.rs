@@ -0,0 +1,2176 @@
+//! Integration tests for the `JsonRpc` handler.
+//!
+//! These tests call `JsonRpc::call()` directly instead of going through an
+//! example binary, so they can register handlers that exercise specific
+//! dispatch behavior.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test jsonrpc
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use json_rpc::cancellation::CancellationToken;
+    use json_rpc::middleware::{Middleware, Next};
+    use json_rpc::{
+        BatchFormat, CallOutcome, Error, ErrorMessages, JsonParser, JsonRpc, MatchKind, Message,
+        MethodStats, RawJson, Request, RequestContext, RequestId, Response, ResultBuilder,
+    };
+    use serde_json::Value;
+    use tokio::io::AsyncWrite;
+
+    async fn exact(_params: Value) -> Result<String, Error> {
+        Ok("exact".to_string())
+    }
+
+    async fn prefix(_params: Value) -> Result<String, Error> {
+        Ok("prefix".to_string())
+    }
+
+    async fn longer_prefix(_params: Value) -> Result<String, Error> {
+        Ok("longer_prefix".to_string())
+    }
+
+    async fn fallback(method: String, _params: Value) -> Result<String, Error> {
+        Ok(format!("fallback:{}", method))
+    }
+
+    fn request(method: &str) -> String {
+        format!(r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#, method)
+    }
+
+    fn overlapping() -> JsonRpc {
+        JsonRpc::new()
+            .add("doc/open", exact)
+            .add("doc/close", exact)
+            .alias("doc/open", "doc/close")
+            .alias("doc/shut", "doc/close")
+            .add_prefix("doc/", prefix)
+            .add_prefix("doc/sh", longer_prefix)
+            .add_fallback(fallback)
+    }
+
+    #[tokio::test]
+    async fn exact_is_preferred_over_alias() {
+        let json_rpc = overlapping();
+        assert_eq!(json_rpc.resolve("doc/open"), Some(MatchKind::Exact));
+        let response = json_rpc.call(&request("doc/open")).await.unwrap();
+        assert_eq!(response, r#"{"jsonrpc":"2.0","result":"exact","id":1}"#);
+    }
+
+    #[tokio::test]
+    async fn alias_is_preferred_over_prefix() {
+        let json_rpc = overlapping();
+        assert_eq!(json_rpc.resolve("doc/shut"), Some(MatchKind::Alias));
+        let response = json_rpc.call(&request("doc/shut")).await.unwrap();
+        assert_eq!(response, r#"{"jsonrpc":"2.0","result":"exact","id":1}"#);
+    }
+
+    #[tokio::test]
+    async fn prefix_is_preferred_over_fallback() {
+        let json_rpc = overlapping();
+        assert_eq!(json_rpc.resolve("doc/save"), Some(MatchKind::Prefix));
+        let response = json_rpc.call(&request("doc/save")).await.unwrap();
+        assert_eq!(response, r#"{"jsonrpc":"2.0","result":"prefix","id":1}"#);
+    }
+
+    #[tokio::test]
+    async fn longest_prefix_wins() {
+        let json_rpc = overlapping();
+        let response = json_rpc.call(&request("doc/show")).await.unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","result":"longer_prefix","id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn fallback_is_used_when_nothing_else_matches() {
+        let json_rpc = overlapping();
+        assert_eq!(json_rpc.resolve("other"), Some(MatchKind::Fallback));
+        let response = json_rpc.call(&request("other")).await.unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","result":"fallback:other","id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn method_not_found_without_fallback() {
+        let json_rpc = JsonRpc::new()
+            .add("doc/open", exact)
+            .alias("missing", "not_registered");
+        assert_eq!(json_rpc.resolve("missing"), None);
+        let response = json_rpc.call(&request("missing")).await.unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Unknown method: missing"},"id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn alias_resolves_to_contextual_method() {
+        let json_rpc = JsonRpc::new()
+            .add_with_context("doc/close", invoked_method)
+            .alias("doc/shut", "doc/close");
+        assert_eq!(json_rpc.resolve("doc/shut"), Some(MatchKind::Alias));
+        let response = json_rpc.call(&request("doc/shut")).await.unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"]["id"], 1);
+    }
+
+    #[test]
+    fn match_kind_orders_by_precedence() {
+        assert!(MatchKind::Exact < MatchKind::Alias);
+        assert!(MatchKind::Alias < MatchKind::Prefix);
+        assert!(MatchKind::Prefix < MatchKind::Fallback);
+    }
+
+    /// A parser that accepts single-quoted strings by rewriting them first.
+    struct SingleQuoteParser;
+
+    impl JsonParser for SingleQuoteParser {
+        fn parse(&self, input: &str) -> Result<Value, Error> {
+            Ok(serde_json::from_str(&input.replace('\'', "\""))?)
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_parser_is_used_for_incoming_messages() {
+        let json_rpc = JsonRpc::new()
+            .with_parser(SingleQuoteParser)
+            .add("doc/open", exact);
+        let response = json_rpc
+            .call("{'jsonrpc':'2.0','method':'doc/open','id':1}")
+            .await
+            .unwrap();
+        assert_eq!(response, r#"{"jsonrpc":"2.0","result":"exact","id":1}"#);
+    }
+
+    #[tokio::test]
+    async fn default_parser_rejects_what_custom_parser_accepts() {
+        let json_rpc = JsonRpc::new().add("doc/open", exact);
+        let response = json_rpc
+            .call("{'jsonrpc':'2.0','method':'doc/open','id':1}")
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#
+        );
+    }
+
+    /// Build a handler that counts how many times it ran.
+    fn counting_rpc(calls: Arc<AtomicUsize>, ttl: Duration) -> JsonRpc {
+        JsonRpc::new()
+            .with_idempotency(ttl)
+            .add("charge", move |amount: u32| {
+                let calls = Arc::clone(&calls);
+                async move {
+                    let count = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    Ok(format!("charged {} (call {})", amount, count))
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn duplicate_keyed_request_runs_handler_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60));
+        let request =
+            r#"{"jsonrpc":"2.0","method":"charge","params":42,"id":1,"idempotencyKey":"order-7"}"#;
+
+        let first = json_rpc.call(request).await.unwrap();
+        let second = json_rpc.call(request).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+        assert_eq!(
+            first,
+            r#"{"jsonrpc":"2.0","result":"charged 42 (call 1)","id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_without_key_are_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60));
+        let request = r#"{"jsonrpc":"2.0","method":"charge","params":42,"id":1}"#;
+
+        json_rpc.call(request).await.unwrap();
+        json_rpc.call(request).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_key_runs_handler_again() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_millis(10));
+        let request =
+            r#"{"jsonrpc":"2.0","method":"charge","params":42,"id":1,"idempotencyKey":"order-7"}"#;
+
+        json_rpc.call(request).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        json_rpc.call(request).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn duplicate_keyed_request_in_batch_uses_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60));
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"a"},
+            {"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"a"},
+            {"jsonrpc":"2.0","method":"charge","params":2,"id":2,"idempotencyKey":"b"}
+        ]"#;
+
+        let response = json_rpc.call(request).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            response,
+            r#"[{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":1},{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":1},{"jsonrpc":"2.0","result":"charged 2 (call 2)","id":2}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_response_takes_the_retry_id() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60));
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"charge","params":42,"id":1,"idempotencyKey":"k"}"#)
+            .await
+            .unwrap();
+        let retry = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"charge","params":42,"id":2,"idempotencyKey":"k"}"#)
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            retry,
+            r#"{"jsonrpc":"2.0","result":"charged 42 (call 1)","id":2}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate_key_in_batch_keeps_each_id() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60));
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"k"},
+            {"jsonrpc":"2.0","method":"charge","params":1,"id":2,"idempotencyKey":"k"}
+        ]"#;
+
+        let response = json_rpc.call(request).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            response,
+            r#"[{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":1},{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":2}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn reused_key_for_another_call_runs_again() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&calls);
+        let json_rpc = counting_rpc(Arc::clone(&calls), Duration::from_secs(60)).add(
+            "refund",
+            move |amount: u32| {
+                let calls = Arc::clone(&counted);
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(format!("refunded {}", amount))
+                }
+            },
+        );
+
+        let first = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"k"}"#)
+            .await;
+        let other_params = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"charge","params":2,"id":2,"idempotencyKey":"k"}"#)
+            .await;
+        let other_method = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"refund","params":1,"id":3,"idempotencyKey":"k"}"#)
+            .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            first.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":1}"#)
+        );
+        assert_eq!(
+            other_params.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"charged 2 (call 2)","id":2}"#)
+        );
+        assert_eq!(
+            other_method.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"refunded 1","id":3}"#)
+        );
+    }
+
+    /// Build a counting handler that takes 50ms per call.
+    fn slow_counting_rpc(calls: Arc<AtomicUsize>) -> JsonRpc {
+        JsonRpc::new()
+            .with_idempotency(Duration::from_secs(60))
+            .add("charge", move |amount: u32| {
+                let calls = Arc::clone(&calls);
+                async move {
+                    let count = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(format!("charged {} (call {})", amount, count))
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn concurrent_duplicate_waits_for_the_first_response() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = slow_counting_rpc(Arc::clone(&calls));
+
+        let (first, second) = tokio::join!(
+            json_rpc.call(
+                r#"{"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"k"}"#
+            ),
+            json_rpc.call(
+                r#"{"jsonrpc":"2.0","method":"charge","params":1,"id":2,"idempotencyKey":"k"}"#
+            ),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            first.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":1}"#)
+        );
+        assert_eq!(
+            second.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"charged 1 (call 1)","id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate_of_abandoned_request_runs_again() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let json_rpc = slow_counting_rpc(Arc::clone(&calls));
+        let request =
+            r#"{"jsonrpc":"2.0","method":"charge","params":1,"id":1,"idempotencyKey":"k"}"#;
+
+        let abandoned = tokio::time::timeout(Duration::from_millis(10), json_rpc.call(request));
+        assert!(abandoned.await.is_err());
+
+        let retry = tokio::time::timeout(Duration::from_secs(1), json_rpc.call(request))
+            .await
+            .expect("retry waited for an abandoned request");
+        assert_eq!(
+            retry.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"charged 1 (call 2)","id":1}"#)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn validate_rejects_empty_handler() {
+        let error = JsonRpc::new().validate().unwrap_err();
+        assert_eq!(error.to_string(), "Protocol error: no methods registered");
+    }
+
+    #[test]
+    fn validate_accepts_registered_handlers() {
+        assert!(JsonRpc::new().add("doc/open", exact).validate().is_ok());
+        assert!(JsonRpc::new().add_prefix("doc/", prefix).validate().is_ok());
+        assert!(JsonRpc::new().add_fallback(fallback).validate().is_ok());
+    }
+
+    /// A writer that keeps the written bytes and the size of the largest write.
+    #[derive(Default)]
+    struct RecordingWriter {
+        data: Vec<u8>,
+        largest_write: usize,
+    }
+
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.largest_write = self.largest_write.max(buf.len());
+            self.data.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    #[tokio::test]
+    async fn call_to_writer_streams_large_batch() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        let requests: Vec<Value> = (0..1000)
+            .map(|id| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "echo",
+                    "params": {"payload": "x".repeat(100)},
+                    "id": id,
+                })
+            })
+            .collect();
+        let request = Value::Array(requests).to_string();
+
+        let mut writer = RecordingWriter::default();
+        json_rpc
+            .call_to_writer(&request, &mut writer)
+            .await
+            .unwrap();
+
+        let expected = json_rpc.call(&request).await.unwrap() + "\n";
+        let written = String::from_utf8(writer.data).unwrap();
+        assert_eq!(written, expected);
+        assert_eq!(written.lines().count(), 1);
+        assert!(writer.largest_write < expected.len() / 100);
+    }
+
+    #[tokio::test]
+    async fn ndjson_batch_format_writes_one_line_per_response() {
+        let json_rpc = JsonRpc::new()
+            .add("exact", exact)
+            .with_batch_format(BatchFormat::Ndjson);
+        let request = r#"[
+            {"jsonrpc":"2.0","method":"exact","id":1},
+            {"jsonrpc":"2.0","method":"exact"},
+            {"jsonrpc":"2.0","method":"missing","id":2}
+        ]"#;
+        let expected = [
+            r#"{"jsonrpc":"2.0","result":"exact","id":1}"#,
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Unknown method: missing"},"id":2}"#,
+        ];
+
+        let response = json_rpc.call(request).await.unwrap();
+        assert_eq!(response.lines().collect::<Vec<_>>(), expected);
+
+        let mut written = Vec::new();
+        json_rpc
+            .call_to_writer(request, &mut written)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            expected.join("\n") + "\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn call_to_writer_writes_single_response_line() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        let mut writer = RecordingWriter::default();
+        json_rpc
+            .call_to_writer(
+                r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}"#,
+                &mut writer,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.data).unwrap(),
+            "{\"jsonrpc\":\"2.0\",\"result\":\"hi\",\"id\":1}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn call_to_writer_writes_nothing_for_notification() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        let mut writer = RecordingWriter::default();
+        json_rpc
+            .call_to_writer(
+                r#"{"jsonrpc":"2.0","method":"echo","params":"hi"}"#,
+                &mut writer,
+            )
+            .await
+            .unwrap();
+        assert!(writer.data.is_empty());
+    }
+
+    async fn divide(params: (i32, i32)) -> Result<i32, Error> {
+        if params.1 == 0 {
+            return Err(Error::rpc(-32000, "Division by zero"));
+        }
+        Ok(params.0 / params.1)
+    }
+
+    #[tokio::test]
+    async fn stats_count_success_and_error_per_method() {
+        let json_rpc = JsonRpc::new().add("divide", divide).add("echo", echo);
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"divide","params":[4,2],"id":1}"#)
+            .await;
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"divide","params":[4,0],"id":2}"#)
+            .await;
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"divide","params":"bad","id":3}"#)
+            .await;
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":1}"#)
+            .await;
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"missing","id":4}"#)
+            .await;
+
+        let stats = json_rpc.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats["divide"],
+            MethodStats {
+                success: 1,
+                error: 2
+            }
+        );
+        assert_eq!(
+            stats["echo"],
+            MethodStats {
+                success: 1,
+                error: 0
+            }
+        );
+    }
+
+    fn localized() -> JsonRpc {
+        JsonRpc::new()
+            .with_error_messages(ErrorMessages {
+                parse_error: "Erreur d'analyse".to_string(),
+                invalid_request: "Requête invalide".to_string(),
+            })
+            .add("echo", echo)
+    }
+
+    #[tokio::test]
+    async fn custom_parse_error_message() {
+        let response = localized().call("not json").await.unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Erreur d'analyse"},"id":null}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_invalid_request_message() {
+        let response = localized()
+            .call(r#"{"jsonrpc":"1.0","method":"echo","id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Requête invalide"},"id":1}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn boolean_and_structured_ids_are_invalid_requests() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+
+        for id in ["true", "[1]", r#"{"n":1}"#] {
+            let request = format!(r#"{{"jsonrpc":"2.0","method":"echo","id":{}}}"#, id);
+            assert_eq!(
+                json_rpc.call(&request).await.as_deref(),
+                Some(
+                    r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#
+                ),
+                "id {}",
+                id
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn byte_order_mark_and_whitespace_are_ignored() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+
+        for request in [
+            "\u{feff}{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"params\":\"hi\",\"id\":1}",
+            "\u{feff} \r\n\t{\"jsonrpc\":\"2.0\",\"method\":\"echo\",\"params\":\"hi\",\"id\":1} \r\n",
+        ] {
+            assert_eq!(
+                json_rpc.call(request).await.as_deref(),
+                Some(r#"{"jsonrpc":"2.0","result":"hi","id":1}"#)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_method_not_found_hides_method_name() {
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .with_method_not_found(|_method| json_rpc::types::Error::method_not_found("Not found"));
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"admin.secret","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Not found"},"id":1}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_invalid_request_message_in_batch() {
+        let response = localized()
+            .call(r#"[1,{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}]"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            r#"[{"jsonrpc":"2.0","error":{"code":-32600,"message":"Requête invalide"},"id":null},{"jsonrpc":"2.0","result":"hi","id":1}]"#
+        );
+    }
+
+    async fn lookup_user(id: u32) -> String {
+        format!("user-{}", id)
+    }
+
+    async fn count_posts(id: u32) -> u32 {
+        id * 10
+    }
+
+    async fn profile(id: u32, mut result: ResultBuilder) -> Result<ResultBuilder, Error> {
+        result.set("name", lookup_user(id).await)?;
+        result.set("posts", count_posts(id).await)?;
+        result.set("tags", ["a", "b"])?;
+        Ok(result)
+    }
+
+    #[tokio::test]
+    async fn builder_handler_returns_multi_field_result() {
+        let json_rpc = JsonRpc::new().add_with_builder("profile", profile);
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"profile","params":7,"id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            r#"{"jsonrpc":"2.0","result":{"name":"user-7","posts":70,"tags":["a","b"]},"id":1}"#
+        );
+    }
+
+    #[test]
+    fn builder_replaces_existing_field() {
+        let mut result = ResultBuilder::new();
+        result.set("a", 1).unwrap();
+        result.set("a", 2).unwrap();
+        assert!(result.contains("a"));
+        assert_eq!(result.build(), serde_json::json!({"a": 2}));
+    }
+
+    const CACHED_REPORT: &str = r#"{"source": "cache",  "hits": [1, 2.50, 1e2, null]}"#;
+
+    async fn cached_report(_params: ()) -> Result<RawJson, Error> {
+        RawJson::new(CACHED_REPORT.to_string())
+    }
+
+    #[tokio::test]
+    async fn raw_json_result_is_embedded_as_is() {
+        let json_rpc = JsonRpc::new().add("report", cached_report);
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"report","id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            format!(r#"{{"jsonrpc":"2.0","result":{},"id":1}}"#, CACHED_REPORT)
+        );
+
+        let response = json_rpc
+            .call(r#"[{"jsonrpc":"2.0","method":"report","id":1},{"jsonrpc":"2.0","method":"report","id":2}]"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            format!(
+                r#"[{{"jsonrpc":"2.0","result":{0},"id":1}},{{"jsonrpc":"2.0","result":{0},"id":2}}]"#,
+                CACHED_REPORT
+            )
+        );
+    }
+
+    #[test]
+    fn raw_result_compares_by_its_wire_text() {
+        let raw = |text: &str| RawJson::new(text.to_string()).unwrap();
+        let id = RequestId::Number(1);
+
+        let as_raw = Response::raw_success(id.clone(), raw(r#"{"a":[1,2]}"#));
+        let as_value = Response::success(id.clone(), serde_json::json!({"a": [1, 2]}));
+        assert_eq!(as_raw, as_value);
+        assert_eq!(
+            serde_json::to_string(&as_raw).unwrap(),
+            serde_json::to_string(&as_value).unwrap()
+        );
+        let hash = |response: &Response| {
+            let mut hasher = DefaultHasher::new();
+            response.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&as_raw), hash(&as_value));
+
+        let spaced = Response::raw_success(id.clone(), raw(r#"{"a": [1, 2]}"#));
+        assert_ne!(spaced, as_value);
+
+        let mut both = as_value.clone();
+        both.raw_result = Some(raw("1"));
+        assert!(both.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn spec_check_covers_raw_results() {
+        let request = r#"{"jsonrpc":"2.0","method":"report","id":null}"#;
+
+        let json_rpc = JsonRpc::new().add("report", cached_report);
+        assert_eq!(
+            json_rpc.call(request).await,
+            Some(format!(
+                r#"{{"jsonrpc":"2.0","result":{},"id":null}}"#,
+                CACHED_REPORT
+            ))
+        );
+
+        let json_rpc = JsonRpc::new()
+            .add("report", cached_report)
+            .with_spec_check(true);
+        assert_eq!(
+            json_rpc.call(request).await.as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal error"},"id":null}"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn frozen_handler_is_shared_across_tasks() {
+        let json_rpc = JsonRpc::new().add("echo", echo).freeze();
+
+        let tasks: Vec<_> = (0..4)
+            .map(|id| {
+                let json_rpc = json_rpc.clone();
+                tokio::spawn(async move {
+                    let request = format!(
+                        r#"{{"jsonrpc":"2.0","method":"echo","params":{},"id":{}}}"#,
+                        id, id
+                    );
+                    json_rpc.call(&request).await.unwrap()
+                })
+            })
+            .collect();
+
+        for (id, task) in tasks.into_iter().enumerate() {
+            assert_eq!(
+                task.await.unwrap(),
+                format!(r#"{{"jsonrpc":"2.0","result":{},"id":{}}}"#, id, id)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_after_freeze_errors() {
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .require_flag("echo", "echo-enabled")
+            .freeze();
+
+        let error = json_rpc.insert("late", echo).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Protocol error: cannot register late on a frozen handler"
+        );
+        assert_eq!(json_rpc.resolve("late"), None);
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"late","params":1,"id":1}"#)
+            .await
+            .unwrap();
+        assert!(response.contains("-32601"));
+
+        // Feature flags are runtime state and still switch on a frozen handler.
+        json_rpc.set_flag("echo-enabled", true);
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":2}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":1,"id":2}"#)
+        );
+        assert!(Arc::ptr_eq(&json_rpc.shared(), &json_rpc.clone().shared()));
+    }
+
+    async fn register(params: Value) -> Result<Value, Error> {
+        Err(Error::rpc_with_data(
+            -32000,
+            "Validation failed",
+            serde_json::json!({ "fields": { "email": params["email"] } }),
+        ))
+    }
+
+    #[tokio::test]
+    async fn rpc_error_data_is_sent_in_error_object() {
+        let json_rpc = JsonRpc::new().add("register", register);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"register","params":{"email":"x"},"id":1}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"Validation failed","data":{"fields":{"email":"x"}}},"id":1}"#
+            )
+        );
+    }
+
+    async fn fail_with(kind: String) -> Result<(), Error> {
+        Err(match kind.as_str() {
+            "parse" => Error::parse_error("bad input"),
+            "method" => Error::method_not_found("no such operation"),
+            "params" => Error::invalid_params("x is required"),
+            _ => Error::internal_error("storage unavailable"),
+        })
+    }
+
+    #[tokio::test]
+    async fn responses_compare_by_value() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":{"b":1,"a":[true]},"id":"x"}"#)
+            .await
+            .unwrap();
+
+        let response: Response = serde_json::from_str(&response).unwrap();
+        assert_eq!(
+            response,
+            Response::success(
+                RequestId::String("x".to_string()),
+                serde_json::json!({"a": [true], "b": 1})
+            )
+        );
+
+        let request = Request::new(RequestId::Number(1), "echo", None);
+        let requests: std::collections::HashSet<_> = [request.clone(), request].into();
+        assert_eq!(requests.len(), 1);
+    }
+
+    #[test]
+    fn internal_errors_map_to_wire_errors() {
+        let serde_error = || serde_json::from_str::<i32>("\"x\"").unwrap_err();
+        let cases = [
+            (
+                Error::rpc_with_data(-32000, "Failed", serde_json::json!([1])),
+                serde_json::json!({"code": -32000, "message": "Failed", "data": [1]}),
+            ),
+            (
+                Error::Cancelled,
+                serde_json::json!({"code": -32800, "message": "Request cancelled"}),
+            ),
+            (
+                Error::InvalidParams(serde_error()),
+                serde_json::json!({
+                    "code": -32602,
+                    "message": "Invalid params",
+                    "data": "invalid type: string \"x\", expected i32 at line 1 column 3",
+                }),
+            ),
+            (
+                Error::ParseError(serde_error()),
+                serde_json::json!({
+                    "code": -32603,
+                    "message": "Protocol error: invalid type: string \"x\", expected i32 at line 1 column 3",
+                }),
+            ),
+            (
+                Error::protocol("broken"),
+                serde_json::json!({"code": -32603, "message": "Protocol error: broken"}),
+            ),
+            (
+                Error::invalid_request("bad"),
+                serde_json::json!({"code": -32603, "message": "Invalid Request: bad"}),
+            ),
+        ];
+
+        for (error, expected) in cases {
+            let wire = json_rpc::types::Error::from(&error);
+            assert_eq!(serde_json::to_value(&wire).unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn standard_error_constructors_use_canonical_codes() {
+        let json_rpc = JsonRpc::new().add("fail", fail_with);
+
+        for (kind, code, message) in [
+            ("parse", -32700, "bad input"),
+            ("method", -32601, "no such operation"),
+            ("params", -32602, "x is required"),
+            ("internal", -32603, "storage unavailable"),
+        ] {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"fail","params":"{}","id":1}}"#,
+                kind
+            );
+            let response: Value =
+                serde_json::from_str(&json_rpc.call(&request).await.unwrap()).unwrap();
+            assert_eq!(
+                response["error"],
+                serde_json::json!({ "code": code, "message": message })
+            );
+        }
+    }
+
+    async fn halve(n: i64) -> Result<i64, Error> {
+        Ok(n / 2)
+    }
+
+    fn positive(n: &i64) -> Result<(), String> {
+        match *n > 0 {
+            true => Ok(()),
+            false => Err(format!("n must be positive, got {}", n)),
+        }
+    }
+
+    #[tokio::test]
+    async fn validated_params_reject_bad_values() {
+        let json_rpc = JsonRpc::new().add_validated("halve", positive, halve);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"halve","params":8,"id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":4,"id":1}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"halve","params":-3,"id":2}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"n must be positive, got -3"},"id":2}"#
+            )
+        );
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Greeting {
+        name: String,
+        #[serde(default)]
+        excited: bool,
+    }
+
+    async fn greet(params: Greeting) -> Result<String, Error> {
+        let mark = if params.excited { "!" } else { "." };
+        Ok(format!("Hello, {}{}", params.name, mark))
+    }
+
+    #[tokio::test]
+    async fn named_params_default_missing_fields() {
+        let json_rpc = JsonRpc::new().add_named("greet", greet);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"greet","params":{"name":"Ada"},"id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"Hello, Ada.","id":1}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(
+                    r#"{"jsonrpc":"2.0","method":"greet","params":{"name":"Ada","excited":true},"id":2}"#
+                )
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"Hello, Ada!","id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn named_params_reject_arrays() {
+        let json_rpc = JsonRpc::new().add_named("greet", greet);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"greet","params":["Ada",true],"id":1}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params","data":"expected named params as an object"},"id":1}"#
+            )
+        );
+        assert_eq!(
+            json_rpc.method_signatures()["greet"].0,
+            std::any::type_name::<Greeting>()
+        );
+    }
+
+    #[tokio::test]
+    async fn call_outcome_flags_match_response() {
+        let json_rpc = JsonRpc::new().add("exact", exact);
+
+        let (response, outcome) = json_rpc
+            .call_with_outcome(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+            .await;
+        assert!(response.is_some());
+        assert_eq!(outcome, CallOutcome::default());
+
+        let (response, outcome) = json_rpc
+            .call_with_outcome(r#"[{"jsonrpc":"2.0","method":"exact","id":1},{"jsonrpc":"2.0","method":"missing","id":2}]"#)
+            .await;
+        assert!(response.is_some());
+        assert!(outcome.is_error);
+        assert_eq!(outcome.code, Some(-32601));
+        assert!(!outcome.is_notification);
+
+        let (response, outcome) = json_rpc
+            .call_with_outcome(r#"{"jsonrpc":"2.0","method":"exact"}"#)
+            .await;
+        assert_eq!(response, None);
+        assert_eq!(
+            outcome,
+            CallOutcome {
+                is_error: false,
+                code: None,
+                is_notification: true
+            }
+        );
+    }
+
+    struct RequireToken;
+
+    impl Middleware for RequireToken {
+        async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+            let token = request.params.as_ref().and_then(|p| p.get("token"));
+            if token != Some(&Value::from("secret")) {
+                let error = json_rpc::types::Error::new(-32099, "Unauthorized", None);
+                return Response::error(request.id, error);
+            }
+            next.run(request).await
+        }
+    }
+
+    struct Tag(&'static str, Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+    impl Middleware for Tag {
+        async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+            self.1.lock().unwrap().push(self.0);
+            next.run(request).await
+        }
+    }
+
+    struct DropId;
+
+    impl Middleware for DropId {
+        async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+            let mut response = next.run(request).await;
+            response.id = RequestId::Null;
+            response
+        }
+    }
+
+    #[tokio::test]
+    async fn ids_survive_middleware_and_batches() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .with_middleware(Tag("outer", Arc::clone(&calls)));
+
+        let response = json_rpc
+            .call(
+                r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":18446744073709551615},{"jsonrpc":"2.0","method":"echo","params":2,"id":"b"},{"jsonrpc":"2.0","method":"missing","id":-3}]"#,
+            )
+            .await
+            .unwrap();
+        let ids: Vec<_> = serde_json::from_str::<Vec<Value>>(&response)
+            .unwrap()
+            .into_iter()
+            .map(|response| response["id"].to_string())
+            .collect();
+        assert_eq!(ids, ["18446744073709551615", r#""b""#, "-3"]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "Response id does not match request id")]
+    async fn dropped_response_id_is_caught_in_debug_builds() {
+        let json_rpc = JsonRpc::new().add("echo", echo).with_middleware(DropId);
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":7}"#)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn idempotent_retry_with_new_id_passes_id_check() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .with_idempotency(Duration::from_secs(60))
+            .with_middleware(Tag("outer", Arc::clone(&calls)));
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":7,"idempotencyKey":"k"}"#)
+            .await
+            .unwrap();
+        let retry = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":8,"idempotencyKey":"k"}"#)
+            .await
+            .unwrap();
+        assert_eq!(retry, r#"{"jsonrpc":"2.0","result":1,"id":8}"#);
+    }
+
+    #[tokio::test]
+    async fn middleware_short_circuits_before_handler() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let json_rpc = JsonRpc::new()
+            .add("count", move |_params: Value| {
+                let counter = Arc::clone(&counter);
+                async move { Ok::<_, Error>(counter.fetch_add(1, Ordering::SeqCst) + 1) }
+            })
+            .with_middleware(RequireToken);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"count","params":{},"id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","error":{"code":-32099,"message":"Unauthorized"},"id":1}"#)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"count","params":{"token":"secret"},"id":2}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":1,"id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware_runs_in_registration_order() {
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let json_rpc = JsonRpc::new()
+            .add("exact", exact)
+            .with_middleware(Tag("outer", Arc::clone(&order)))
+            .with_middleware(Tag("inner", Arc::clone(&order)));
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(*order.lock().unwrap(), ["outer", "inner"]);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_error_keeps_details_in_data() {
+        let json_rpc = JsonRpc::new().add("divide", divide);
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"divide","params":{"a":1},"id":1}"#)
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+
+        let error = response["error"].as_object().unwrap();
+        assert_eq!(error.len(), 3);
+        assert_eq!(error["code"], -32602);
+        assert_eq!(error["message"], "Invalid params");
+        assert_eq!(
+            error["data"],
+            "invalid type: map, expected a tuple of size 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn verbose_errors_attach_serde_details() {
+        let request = r#"{"jsonrpc":"2.0","method":"divide","params":"oops","id":1}"#;
+
+        let quiet = JsonRpc::new().add("divide", divide);
+        let response: Value = serde_json::from_str(&quiet.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(
+            response["error"]["data"]
+                .as_str()
+                .unwrap()
+                .contains("invalid type")
+        );
+
+        let verbose = JsonRpc::new()
+            .add("divide", divide)
+            .with_verbose_errors(true);
+        let response: Value = serde_json::from_str(&verbose.call(request).await.unwrap()).unwrap();
+        let data = &response["error"]["data"];
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(data["category"], "data");
+        assert!(data["line"].is_u64());
+        assert!(data["column"].is_u64());
+        assert!(data["message"].as_str().unwrap().contains("invalid type"));
+    }
+
+    #[tokio::test]
+    async fn blocking_handler_does_not_stall_async_requests() {
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = std::sync::Mutex::new(released);
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .add_blocking("crunch", move |n: u64| {
+                // Hold the blocking thread until the async request finished.
+                released
+                    .lock()
+                    .unwrap()
+                    .recv_timeout(Duration::from_secs(5))
+                    .map_err(|_| Error::rpc(-32000, "not released"))?;
+                Ok((1..=n).product::<u64>())
+            })
+            .freeze();
+
+        let crunch = tokio::spawn({
+            let json_rpc = json_rpc.clone();
+            async move {
+                json_rpc
+                    .call(r#"{"jsonrpc":"2.0","method":"crunch","params":5,"id":1}"#)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":2}"#)
+            .await;
+        assert_eq!(
+            response.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"hi","id":2}"#)
+        );
+
+        release.send(()).unwrap();
+        assert_eq!(
+            crunch.await.unwrap().as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":120,"id":1}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn feature_flag_toggles_method_at_runtime() {
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .alias("ping", "echo")
+            .require_flag("echo", "echo-enabled")
+            .freeze();
+        let request = r#"{"jsonrpc":"2.0","method":"echo","params":"hi","id":1}"#;
+
+        let response: Value = serde_json::from_str(&json_rpc.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+        assert_eq!(json_rpc.resolve("ping"), None);
+
+        json_rpc.set_flag("echo-enabled", true);
+        assert_eq!(
+            json_rpc.call(request).await.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"hi","id":1}"#)
+        );
+        assert_eq!(json_rpc.resolve("ping"), Some(MatchKind::Alias));
+
+        json_rpc.set_flag("echo-enabled", false);
+        let response: Value = serde_json::from_str(&json_rpc.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn feature_flag_gates_message_handlers() {
+        let json_rpc = JsonRpc::new()
+            .add_message_handler("inspect", describe_id)
+            .require_flag("inspect", "inspect-enabled");
+        let request = r#"{"jsonrpc":"2.0","method":"inspect","id":1}"#;
+
+        let response: Value = serde_json::from_str(&json_rpc.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+
+        json_rpc.set_flag("inspect-enabled", true);
+        let response: Value = serde_json::from_str(&json_rpc.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["result"]["idType"], "number");
+    }
+
+    /// A log writer that keeps everything written to it.
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogBuffer {
+        /// Capture log output of the current thread until the guard drops.
+        fn capture() -> (Self, tracing::subscriber::DefaultGuard) {
+            let logs = LogBuffer::default();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer({
+                    let logs = logs.clone();
+                    move || logs.clone()
+                })
+                .with_ansi(false)
+                .with_max_level(tracing::Level::DEBUG)
+                .finish();
+            (logs, tracing::subscriber::set_default(subscriber))
+        }
+
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn spec_check_replaces_malformed_response() {
+        let (logs, _guard) = LogBuffer::capture();
+
+        // Batch entries that are responses are passed through as they are,
+        // including this one with the wrong protocol version.
+        let request = r#"[{"jsonrpc":"1.0","error":{"code":1,"message":"stale"},"id":3}]"#;
+
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        assert_eq!(
+            json_rpc.call(request).await.as_deref(),
+            Some(r#"[{"jsonrpc":"1.0","error":{"code":1,"message":"stale"},"id":3}]"#)
+        );
+
+        let json_rpc = JsonRpc::new().add("echo", echo).with_spec_check(true);
+        assert_eq!(
+            json_rpc.call(request).await.as_deref(),
+            Some(
+                r#"[{"jsonrpc":"2.0","error":{"code":-32603,"message":"Internal error"},"id":3}]"#
+            )
+        );
+
+        let logs = logs.contents();
+        assert!(logs.contains("ERROR"));
+        assert!(logs.contains("violates JSON-RPC 2.0"));
+    }
+
+    #[tokio::test]
+    async fn signed_number_ids_round_trip() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+
+        for id in [-5, 0, i64::MAX, i64::MIN] {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"echo","params":"hi","id":{}}}"#,
+                id
+            );
+            assert_eq!(
+                json_rpc.call(&request).await,
+                Some(format!(r#"{{"jsonrpc":"2.0","result":"hi","id":{}}}"#, id))
+            );
+        }
+
+        // The id is kept even when the request itself is rejected.
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"1.0","method":"echo","id":-7}"#)
+            .await;
+        let response: Value = serde_json::from_str(&response.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32600);
+        assert_eq!(response["id"], -7);
+    }
+
+    #[tokio::test]
+    async fn non_integer_ids_are_echoed_unchanged() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+
+        for id in ["1.0", "-2.5", "18446744073709551615"] {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"echo","params":"hi","id":{}}}"#,
+                id
+            );
+            assert_eq!(
+                json_rpc.call(&request).await,
+                Some(format!(r#"{{"jsonrpc":"2.0","result":"hi","id":{}}}"#, id))
+            );
+
+            let invalid = format!(r#"{{"jsonrpc":"1.0","method":"echo","id":{}}}"#, id);
+            let response = json_rpc.call(&invalid).await.unwrap();
+            assert!(response.ends_with(&format!(r#""id":{}}}"#, id)));
+        }
+    }
+
+    #[tokio::test]
+    async fn numeric_ids_are_echoed_byte_for_byte() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+
+        for id in ["99999999999999999999", "1e2", "1.50", "-2.5E-3"] {
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"echo","params":"hi","id":{}}}"#,
+                id
+            );
+            assert_eq!(
+                json_rpc.call(&request).await,
+                Some(format!(r#"{{"jsonrpc":"2.0","result":"hi","id":{}}}"#, id))
+            );
+        }
+
+        let response = json_rpc
+            .call(r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":1e2},{"jsonrpc":"2.0","method":"echo","params":2,"id":7}]"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            r#"[{"jsonrpc":"2.0","result":1,"id":1e2},{"jsonrpc":"2.0","result":2,"id":7}]"#
+        );
+
+        let response = json_rpc
+            .call(r#"{"jsonrpc":"1.0","method":"echo","id":99999999999999999999}"#)
+            .await
+            .unwrap();
+        assert!(response.ends_with(r#""id":99999999999999999999}"#));
+    }
+
+    #[test]
+    fn raw_ids_display_and_compare() {
+        let id: RequestId = serde_json::from_str("1.0").unwrap();
+        assert_eq!(id, RequestId::Raw(RawJson::new("1.0".to_string()).unwrap()));
+        assert_ne!(id, RequestId::Number(1));
+        assert_eq!(id.to_string(), "1.0");
+
+        let id: RequestId = serde_json::from_str("1e2").unwrap();
+        assert_eq!(id.to_string(), "1e2");
+        assert_eq!(serde_json::to_string(&id).unwrap(), "1e2");
+    }
+
+    #[tokio::test]
+    async fn batch_echo_copies_client_reference() {
+        let json_rpc = JsonRpc::new()
+            .add("echo", echo)
+            .add("divide", divide)
+            .with_batch_echo("clientRef");
+
+        let response = json_rpc
+            .call(
+                r#"[
+                    {"jsonrpc":"2.0","method":"echo","params":"a","id":1,"clientRef":"first"},
+                    {"jsonrpc":"2.0","method":"echo","params":"b","clientRef":"ignored"},
+                    {"jsonrpc":"2.0","method":"divide","params":[1,0],"id":2,"clientRef":{"n":2}},
+                    {"jsonrpc":"2.0","method":"echo","params":"c","id":3}
+                ]"#,
+            )
+            .await;
+
+        assert_eq!(
+            response.as_deref(),
+            Some(concat!(
+                r#"[{"jsonrpc":"2.0","result":"a","id":1,"clientRef":"first"},"#,
+                r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"Division by zero"},"id":2,"clientRef":{"n":2}},"#,
+                r#"{"jsonrpc":"2.0","result":"c","id":3}]"#
+            ))
+        );
+    }
+
+    async fn sleep_ms(ms: u64) -> Result<u64, Error> {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(ms)
+    }
+
+    #[tokio::test]
+    async fn batch_timeout_fails_unfinished_entries() {
+        let json_rpc = JsonRpc::new()
+            .add("sleep", sleep_ms)
+            .with_batch_timeout(Duration::from_millis(150));
+
+        let response = json_rpc
+            .call(
+                r#"[
+                    {"jsonrpc":"2.0","method":"sleep","params":50,"id":1},
+                    {"jsonrpc":"2.0","method":"sleep","params":5000,"id":2},
+                    {"jsonrpc":"2.0","method":"sleep","params":0,"id":3}
+                ]"#,
+            )
+            .await;
+
+        assert_eq!(
+            response.as_deref(),
+            Some(concat!(
+                r#"[{"jsonrpc":"2.0","result":50,"id":1},"#,
+                r#"{"jsonrpc":"2.0","error":{"code":-32001,"message":"Request timed out"},"id":2},"#,
+                r#"{"jsonrpc":"2.0","result":0,"id":3}]"#
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_entries_run_concurrently() {
+        let json_rpc = JsonRpc::new().add("sleep", sleep_ms);
+        let batch: Vec<String> = (1..=10)
+            .map(|id| {
+                format!(
+                    r#"{{"jsonrpc":"2.0","method":"sleep","params":100,"id":{}}}"#,
+                    id
+                )
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        let response = json_rpc
+            .call(&format!("[{}]", batch.join(",")))
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+
+        assert!(elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+        let response: Vec<Value> = serde_json::from_str(&response).unwrap();
+        let ids: Vec<_> = response.iter().map(|r| r["id"].as_i64().unwrap()).collect();
+        assert_eq!(ids, (1..=10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn separate_calls_run_in_parallel() {
+        let json_rpc = Arc::new(JsonRpc::new().add_blocking("block", |ms: u64| {
+            std::thread::sleep(Duration::from_millis(ms));
+            Ok(ms)
+        }));
+
+        let started = std::time::Instant::now();
+        let calls: Vec<_> = (1..=2)
+            .map(|id| {
+                let json_rpc = Arc::clone(&json_rpc);
+                tokio::spawn(async move {
+                    let request = format!(
+                        r#"{{"jsonrpc":"2.0","method":"block","params":200,"id":{}}}"#,
+                        id
+                    );
+                    json_rpc.call(&request).await
+                })
+            })
+            .collect();
+        for call in calls {
+            assert!(call.await.unwrap().unwrap().contains(r#""result":200"#));
+        }
+        let elapsed = started.elapsed();
+
+        assert!(elapsed < Duration::from_millis(350), "took {:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn only_slow_requests_are_logged() {
+        let (logs, _guard) = LogBuffer::capture();
+        let json_rpc = JsonRpc::new()
+            .add("sleep", sleep_ms)
+            .log_slow_requests(Duration::from_millis(50));
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"sleep","params":0,"id":1}"#)
+            .await;
+        assert!(!logs.contents().contains("Slow request"));
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"sleep","params":100,"id":2}"#)
+            .await;
+        let logs = logs.contents();
+        assert_eq!(logs.matches("Slow request").count(), 1);
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("method=sleep"));
+        assert!(logs.contains("id=2"));
+        assert!(logs.contains("params=100"));
+    }
+
+    #[derive(serde::Serialize)]
+    struct DateTime {
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    }
+
+    fn rfc3339(time: &DateTime) -> Result<Value, Error> {
+        Ok(Value::String(format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            time.year, time.month, time.day, time.hour, time.minute, time.second
+        )))
+    }
+
+    async fn release_date(_params: Value) -> Result<DateTime, Error> {
+        Ok(DateTime {
+            year: 2024,
+            month: 5,
+            day: 1,
+            hour: 12,
+            minute: 30,
+            second: 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn result_serializer_overrides_one_method() {
+        let json_rpc = JsonRpc::new()
+            .add_with_serializer("release", release_date, rfc3339)
+            .add("release.raw", release_date);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"release","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"2024-05-01T12:30:00Z","id":1}"#)
+        );
+        let raw = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"release.raw","id":2}"#)
+            .await
+            .unwrap();
+        let raw: Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(raw["result"]["year"], 2024);
+        assert_eq!(raw["result"]["month"], 5);
+    }
+
+    async fn describe_id(message: Message) -> Option<Response> {
+        let Message::Request(request) = message else {
+            return None;
+        };
+        let kind = match &request.id {
+            RequestId::Number(_) | RequestId::Raw(_) => "number",
+            RequestId::String(_) => "string",
+            RequestId::Null => "null",
+        };
+        let result = serde_json::json!({"idType": kind, "params": request.params});
+        Some(Response::success(request.id, result))
+    }
+
+    #[tokio::test]
+    async fn message_handler_builds_its_own_response() {
+        let json_rpc = JsonRpc::new().add_message_handler("inspect", describe_id);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"inspect","params":[1],"id":"abc"}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":{"idType":"string","params":[1]},"id":"abc"}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"inspect","id":7}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":{"idType":"number","params":null},"id":7}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"inspect"}"#)
+                .await,
+            None
+        );
+        assert_eq!(json_rpc.resolve("inspect"), Some(MatchKind::Exact));
+    }
+
+    #[tokio::test]
+    async fn request_timeout_answers_hanging_handler() {
+        let json_rpc = JsonRpc::new()
+            .add("sleep", sleep_ms)
+            .with_request_timeout(Duration::from_millis(50));
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"sleep","params":5000,"id":1}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32001,"message":"Request timed out"},"id":1}"#
+            )
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"sleep","params":0,"id":2}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":0,"id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn client_timeout_tightens_request_timeout() {
+        let json_rpc = JsonRpc::new()
+            .add("sleep", sleep_ms)
+            .with_request_timeout(Duration::from_secs(5));
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"sleep","params":5000,"id":1,"timeoutMs":50}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32001,"message":"Request timed out"},"id":1}"#
+            )
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"sleep","params":0,"id":2,"timeoutMs":50}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":0,"id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn redacted_params_are_masked_in_logs_only() {
+        let (logs, _guard) = LogBuffer::capture();
+        let seen = Arc::new(std::sync::Mutex::new(Value::Null));
+        let json_rpc = JsonRpc::new()
+            .add("login", {
+                let seen = Arc::clone(&seen);
+                move |params: Value| {
+                    *seen.lock().unwrap() = params.clone();
+                    async move { Ok(true) }
+                }
+            })
+            .redact_params("login", &["password"]);
+
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"login","params":{"user":"alice","password":"hunter2"},"id":1}"#)
+            .await;
+
+        assert_eq!(seen.lock().unwrap()["password"], "hunter2");
+        let logs = logs.contents();
+        assert!(logs.contains(r#""password":"***""#), "{}", logs);
+        assert!(logs.contains(r#""user":"alice""#));
+        assert!(!logs.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn try_add_rejects_duplicate_methods() {
+        let json_rpc = JsonRpc::new().try_add("exact", exact).unwrap();
+
+        let error = json_rpc.try_add("exact", prefix).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Protocol error: Method already registered: exact"
+        );
+
+        let json_rpc = JsonRpc::new()
+            .add_with_context("exact", invoked_method)
+            .try_add("exact", exact);
+        assert!(matches!(json_rpc, Err(Error::ProtocolError(_))));
+    }
+
+    #[tokio::test]
+    async fn add_replaces_every_kind_of_handler() {
+        let request = r#"{"jsonrpc":"2.0","method":"exact","id":1}"#;
+        let expected = Some(r#"{"jsonrpc":"2.0","result":"prefix","id":1}"#);
+
+        let json_rpc = JsonRpc::new()
+            .add_with_context("exact", invoked_method)
+            .add("exact", prefix);
+        assert_eq!(json_rpc.call(request).await.as_deref(), expected);
+
+        let json_rpc = JsonRpc::new()
+            .add_message_handler("exact", describe_id)
+            .add("exact", prefix);
+        assert_eq!(json_rpc.call(request).await.as_deref(), expected);
+
+        let json_rpc = JsonRpc::new()
+            .add("exact", exact)
+            .add_with_context("exact", invoked_method);
+        let response: Value = serde_json::from_str(&json_rpc.call(request).await.unwrap()).unwrap();
+        assert_eq!(response["result"]["method"], "exact");
+    }
+
+    #[tokio::test]
+    async fn add_or_replace_overrides_earlier_handler() {
+        let json_rpc = JsonRpc::new()
+            .add_with_context("exact", invoked_method)
+            .add_or_replace("exact", prefix);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"prefix","id":1}"#)
+        );
+    }
+
+    json_rpc::define_methods! {
+        struct Api {
+            EXACT = "api.exact" => exact,
+            ECHO = "api.echo" => echo,
+        }
+    }
+
+    #[tokio::test]
+    async fn define_methods_registers_declared_handlers() {
+        assert_eq!(Api::METHODS, [Api::EXACT, Api::ECHO]);
+        let json_rpc = Api::json_rpc();
+
+        let request = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#, Api::EXACT);
+        assert_eq!(
+            json_rpc.call(&request).await.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"exact","id":1}"#)
+        );
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","params":[1],"id":2}}"#,
+            Api::ECHO
+        );
+        assert_eq!(
+            json_rpc.call(&request).await.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":[1],"id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_initialize() {
+        let json_rpc = JsonRpc::new()
+            .add("initialize", echo)
+            .add("exact", exact)
+            .require_initialize("initialize");
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+                .await
+                .as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"Server not initialized"},"id":1}"#
+            )
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"initialize","params":{},"id":2}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":{},"id":2}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exact","id":3}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"exact","id":3}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_dispatches_to_methods_from_both() {
+        let core = JsonRpc::new().add("exact", exact);
+        let admin = JsonRpc::new()
+            .add("admin.echo", echo)
+            .alias("admin.repeat", "admin.echo");
+        let json_rpc = core.merge(admin).unwrap();
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"exact","id":1}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"admin.repeat","params":[2],"id":2}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":[2],"id":2}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_rejects_conflicts_unless_replacing() {
+        let error = JsonRpc::new()
+            .add("exact", exact)
+            .merge(JsonRpc::new().add_with_context("exact", invoked_method))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Protocol error: Method already registered: exact"
+        );
+
+        let json_rpc = JsonRpc::new()
+            .add_with_context("exact", invoked_method)
+            .merge_or_replace(JsonRpc::new().add("exact", prefix));
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exact","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"prefix","id":1}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_notifications_go_to_dead_letters() {
+        let json_rpc = JsonRpc::new().add("exact", exact).with_dead_letters(2);
+
+        for method in ["first", "exact", "second", "third"] {
+            let notification = format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","params":{{"n":1}}}}"#,
+                method
+            );
+            assert_eq!(json_rpc.call(&notification).await, None);
+        }
+
+        assert_eq!(json_rpc.unknown_notifications(), 3);
+        let letters = json_rpc.dead_letters();
+        let methods: Vec<_> = letters.iter().map(|n| n.method.as_str()).collect();
+        assert_eq!(methods, ["second", "third"]);
+        assert_eq!(letters[0].params, Some(serde_json::json!({"n": 1})));
+    }
+
+    #[tokio::test]
+    async fn unhandled_notification_callback_gets_method_name() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let json_rpc = JsonRpc::new()
+            .add("exact", exact)
+            .on_unhandled_notification(move |method| {
+                recorder.lock().unwrap().push(method.to_string());
+            });
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"exatc","params":[]}"#)
+                .await,
+            None
+        );
+        json_rpc.call(r#"{"jsonrpc":"2.0","method":"exact"}"#).await;
+        json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"missing","id":1}"#)
+            .await;
+
+        assert_eq!(*seen.lock().unwrap(), ["exatc"]);
+    }
+
+    #[tokio::test]
+    async fn notification_errors_reach_the_hook() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let json_rpc = JsonRpc::new()
+            .add("fail", fail_with)
+            .add("exact", exact)
+            .on_notification_error(move |method, error| {
+                recorder
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}: {}", method, error));
+            });
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"fail","params":"params"}"#)
+                .await,
+            None
+        );
+        json_rpc.call(r#"{"jsonrpc":"2.0","method":"exact"}"#).await;
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["fail: JSON-RPC error: code=-32602, message=x is required"]
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_registers_methods_in_place() {
+        let mut json_rpc = JsonRpc::new();
+        for (name, value) in [("one", 1), ("two", 2), ("three", 3)] {
+            json_rpc.insert(
+                name,
+                move |_params: Value| async move { Ok::<_, Error>(value) },
+            );
+        }
+
+        for (id, name) in ["one", "two", "three"].into_iter().enumerate() {
+            let request = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":{}}}"#, name, id);
+            assert_eq!(
+                json_rpc.call(&request).await,
+                Some(format!(
+                    r#"{{"jsonrpc":"2.0","result":{},"id":{}}}"#,
+                    id + 1,
+                    id
+                ))
+            );
+        }
+    }
+
+    async fn add_pair(params: (i32, i32)) -> Result<i32, Error> {
+        Ok(params.0 + params.1)
+    }
+
+    #[test]
+    fn method_signatures_report_type_names() {
+        let json_rpc = JsonRpc::new()
+            .add("add", add_pair)
+            .add_with_context("context", invoked_method)
+            .add_prefix("math/", add_pair);
+
+        let signatures = json_rpc.method_signatures();
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(
+            signatures["add"],
+            ("(i32, i32)".to_string(), "i32".to_string())
+        );
+        assert_eq!(
+            signatures["context"],
+            (
+                "serde_json::value::Value".to_string(),
+                "serde_json::value::Value".to_string()
+            )
+        );
+    }
+
+    async fn invoked_method(_params: Value, context: RequestContext) -> Result<Value, Error> {
+        Ok(serde_json::json!({ "method": context.method, "id": context.id }))
+    }
+
+    #[tokio::test]
+    async fn context_handler_sees_method_and_id() {
+        let json_rpc = JsonRpc::new()
+            .add_with_context("first", invoked_method)
+            .add_with_context("second", invoked_method);
+
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"first","id":1}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":{"id":1,"method":"first"},"id":1}"#)
+        );
+        assert_eq!(
+            json_rpc
+                .call(r#"{"jsonrpc":"2.0","method":"second","id":"b"}"#)
+                .await
+                .as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":{"id":"b","method":"second"},"id":"b"}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_notification_cancels_in_flight_request() {
+        let started = Arc::new(tokio::sync::Notify::new());
+        let notify = Arc::clone(&started);
+        let json_rpc = Arc::new(JsonRpc::new().add_cancellable(
+            "wait",
+            move |_params: Value, token: CancellationToken| {
+                let notify = Arc::clone(&notify);
+                async move {
+                    notify.notify_one();
+                    token.cancelled().await;
+                    Err::<Value, _>(Error::Cancelled)
+                }
+            },
+        ));
+
+        let request = tokio::spawn({
+            let json_rpc = Arc::clone(&json_rpc);
+            async move {
+                json_rpc
+                    .call(r#"{"jsonrpc":"2.0","method":"wait","id":7}"#)
+                    .await
+            }
+        });
+        started.notified().await;
+
+        let cancel = json_rpc
+            .call(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":7}}"#)
+            .await;
+        assert_eq!(cancel, None);
+        assert_eq!(
+            request.await.unwrap().as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32800,"message":"Request cancelled"},"id":7}"#
+            )
+        );
+        assert!(!json_rpc.cancel(&RequestId::Number(7)));
+    }
+
+    #[tokio::test]
+    async fn finished_request_keeps_cancel_entry_of_reused_id() {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let json_rpc = Arc::new(
+            JsonRpc::new()
+                .add_cancellable("hold", {
+                    let release = Arc::clone(&release);
+                    move |_params: Value, _token: CancellationToken| {
+                        let release = Arc::clone(&release);
+                        async move {
+                            release.notified().await;
+                            Ok("released")
+                        }
+                    }
+                })
+                .add_cancellable(
+                    "wait",
+                    |_params: Value, token: CancellationToken| async move {
+                        token.cancelled().await;
+                        Err::<Value, _>(Error::Cancelled)
+                    },
+                ),
+        );
+
+        let spawn = |request: &'static str| {
+            let json_rpc = Arc::clone(&json_rpc);
+            tokio::spawn(async move { json_rpc.call(request).await })
+        };
+        let held = spawn(r#"{"jsonrpc":"2.0","method":"hold","id":7}"#);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let waiting = spawn(r#"{"jsonrpc":"2.0","method":"wait","id":7}"#);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        release.notify_one();
+        assert_eq!(
+            held.await.unwrap().as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":"released","id":7}"#)
+        );
+        assert!(json_rpc.cancel(&RequestId::Number(7)));
+        assert_eq!(
+            waiting.await.unwrap().as_deref(),
+            Some(
+                r#"{"jsonrpc":"2.0","error":{"code":-32800,"message":"Request cancelled"},"id":7}"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn streamed_batch_responses_stay_buffered_in_call() {
+        let json_rpc = JsonRpc::new()
+            .add(
+                "echo",
+                |params: Value| async move { Ok::<_, Error>(params) },
+            )
+            .stream_batch_responses(true);
+
+        let response = json_rpc
+            .call(
+                r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":1},{"jsonrpc":"2.0","method":"echo","params":2,"id":2}]"#,
+            )
+            .await;
+        assert_eq!(
+            response.as_deref(),
+            Some(r#"[{"jsonrpc":"2.0","result":1,"id":1},{"jsonrpc":"2.0","result":2,"id":2}]"#)
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Integration tests for recording and replaying transport sessions.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test record
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use json_rpc::record::{Recording, Replay};
+    use json_rpc::{Error, JsonRpc, serve_io};
+    use serde_json::Value;
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    async fn divide(params: (i32, i32)) -> Result<i32, Error> {
+        if params.1 == 0 {
+            return Err(Error::rpc(-32000, "Division by zero"));
+        }
+        Ok(params.0 / params.1)
+    }
+
+    #[tokio::test]
+    async fn replayed_session_gets_identical_responses() {
+        let json_rpc = JsonRpc::new().add("echo", echo).add("divide", divide);
+        let path =
+            std::env::temp_dir().join(format!("json-rpc-session-{}.jsonl", std::process::id()));
+
+        let session = Replay::new(
+            [
+                r#"{"jsonrpc":"2.0","method":"echo","params":["a"],"id":1}"#,
+                r#"{"jsonrpc":"2.0","method":"echo","params":"note"}"#,
+                r#"{"jsonrpc":"2.0","method":"divide","params":[1,0],"id":2}"#,
+                r#"[{"jsonrpc":"2.0","method":"divide","params":[9,3],"id":3},{"jsonrpc":"2.0","method":"missing","id":4}]"#,
+            ]
+            .map(String::from),
+        );
+        let mut recording = Recording::new(session, File::create(&path).unwrap());
+        serve_io(&mut recording, &json_rpc).await.unwrap();
+        let (session, _log) = recording.into_inner();
+        assert_eq!(session.sent().len(), 3);
+
+        let mut replay = Replay::from_reader(BufReader::new(File::open(&path).unwrap())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.expected(), session.sent());
+
+        serve_io(&mut replay, &json_rpc).await.unwrap();
+        assert_eq!(replay.sent(), replay.expected());
+    }
+}
@@ -0,0 +1,102 @@
+//! Integration tests for response hooks over the axum handler.
+//!
+//! This test suite serves a `JsonRpc` with a response hook from an in-process
+//! axum server and checks that the hook sees the exact response body and that
+//! its metadata arrives as HTTP headers.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test response_hook_axum --features axum
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::sync::{Arc, Mutex};
+
+    use axum::Router;
+    use axum::routing::post;
+    use json_rpc::axum::handler;
+    use json_rpc::{Error, JsonRpc};
+    use serde_json::Value;
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    /// Stand-in for an HMAC over the response bytes.
+    fn sign(bytes: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Start an axum server for `json_rpc` on a random local port.
+    async fn start_server(json_rpc: JsonRpc) -> String {
+        let app = Router::new()
+            .route("/jsonrpc", post(handler))
+            .with_state(Arc::new(json_rpc));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}/jsonrpc", addr)
+    }
+
+    #[tokio::test]
+    async fn hook_signs_the_bytes_sent_on_the_wire() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let json_rpc = JsonRpc::new().add("echo", echo).on_response({
+            let seen = Arc::clone(&seen);
+            move |bytes| {
+                seen.lock().unwrap().push(bytes.to_vec());
+                vec![("x-signature".to_string(), sign(bytes))]
+            }
+        });
+        let url = start_server(json_rpc).await;
+
+        let response = reqwest::Client::new()
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(r#"{"jsonrpc":"2.0","method":"echo","params":{"amount":42},"id":1}"#)
+            .send()
+            .await
+            .unwrap();
+        let signature = response.headers()["x-signature"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.bytes().await.unwrap();
+
+        assert_eq!(seen.lock().unwrap().as_slice(), &[body.to_vec()]);
+        assert_eq!(signature, sign(&body));
+    }
+
+    #[tokio::test]
+    async fn hook_is_not_run_for_notifications() {
+        let runs = Arc::new(Mutex::new(0));
+        let json_rpc = JsonRpc::new().add("echo", echo).on_response({
+            let runs = Arc::clone(&runs);
+            move |_| {
+                *runs.lock().unwrap() += 1;
+                Vec::new()
+            }
+        });
+        let url = start_server(json_rpc).await;
+
+        let response = reqwest::Client::new()
+            .post(&url)
+            .body(r#"{"jsonrpc":"2.0","method":"echo","params":"hi"}"#)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+        assert_eq!(*runs.lock().unwrap(), 0);
+    }
+}
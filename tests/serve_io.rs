@@ -0,0 +1,770 @@
+//! Integration tests for serving custom transports with `serve_io`.
+//!
+//! This test suite implements `AsyncJsonRpcIo` over an in-memory
+//! `tokio::io::duplex` pair and drives it with `serve_io`, playing the client
+//! on the other end of the pipe.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test serve_io
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use json_rpc::cancellation::CancellationToken;
+    use json_rpc::io::{ContentLengthIo, LineIo, MAX_HEADER_LINE_LENGTH, MAX_HEADER_LINES};
+    use json_rpc::reload::Registry;
+    use json_rpc::shutdown::ShutdownSignal;
+    use json_rpc::sink::NotificationSink;
+    use json_rpc::test_util::serve_n;
+    use json_rpc::{
+        AsyncJsonRpcIo, Error, JsonRpc, serve_duplex, serve_io, serve_reloadable, serve_with_ready,
+        serve_with_shutdown,
+    };
+    use serde_json::Value;
+    use tokio::io::{
+        AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf,
+    };
+
+    /// Newline-delimited messages over one end of a duplex pipe.
+    struct DuplexLines {
+        reader: BufReader<ReadHalf<DuplexStream>>,
+        writer: WriteHalf<DuplexStream>,
+    }
+
+    impl DuplexLines {
+        fn new(stream: DuplexStream) -> Self {
+            let (reader, writer) = tokio::io::split(stream);
+            Self {
+                reader: BufReader::new(reader),
+                writer,
+            }
+        }
+    }
+
+    impl AsyncJsonRpcIo for DuplexLines {
+        async fn recv(&mut self) -> Result<Option<String>, Error> {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line).await {
+                Ok(0) => Ok(None),
+                Ok(_) => Ok(Some(line)),
+                Err(e) => Err(Error::protocol(e.to_string())),
+            }
+        }
+
+        async fn send(&mut self, message: &str) -> Result<(), Error> {
+            let line = format!("{}\n", message);
+            self.writer
+                .write_all(line.as_bytes())
+                .await
+                .map_err(|e| Error::protocol(e.to_string()))
+        }
+    }
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    #[tokio::test]
+    async fn serves_requests_over_duplex_pipe() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            serve_io(DuplexLines::new(server), &json_rpc).await
+        });
+
+        let mut client = DuplexLines::new(client);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":"ignored"}"#)
+            .await
+            .unwrap();
+        client.send("").await.unwrap();
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":"hello","id":1}"#)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":\"hello\",\"id\":1}\n")
+        );
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn serve_duplex_frames_lines_over_halves() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"[{"jsonrpc":"2.0","method":"echo","params":1,"id":1},{"jsonrpc":"2.0","method":"echo","params":2,"id":2}]"#)
+            .await
+            .unwrap();
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":3,"id":3}"#)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some(
+                "[{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1},{\"jsonrpc\":\"2.0\",\"result\":2,\"id\":2}]\n"
+            )
+        );
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":3,\"id\":3}\n")
+        );
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn serve_with_shutdown_stops_while_waiting() {
+        let (client, server) = tokio::io::duplex(1024);
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            serve_with_shutdown(LineIo::new(reader, writer), &json_rpc, async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n")
+        );
+
+        stop.send(()).unwrap();
+        served.await.unwrap().unwrap();
+        assert_eq!(client.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn serve_with_ready_signals_before_serving() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (client, server) = tokio::io::duplex(1024);
+        let (ready, started) = tokio::sync::oneshot::channel();
+        let served = tokio::spawn({
+            let events = Arc::clone(&events);
+            async move {
+                let json_rpc = JsonRpc::new().add("echo", move |params: Value| {
+                    events.lock().unwrap().push("request");
+                    async move { Ok(params) }
+                });
+                let (reader, writer) = tokio::io::split(server);
+                serve_with_ready(LineIo::new(reader, writer), &json_rpc, ready).await
+            }
+        });
+
+        started.await.unwrap();
+        events.lock().unwrap().push("ready");
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n")
+        );
+        assert_eq!(*events.lock().unwrap(), ["ready", "request"]);
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_closed() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            let io = LineIo::new(reader, writer).with_idle_timeout(Duration::from_millis(50));
+            serve_io(io, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":1}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n")
+        );
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        served.await.unwrap().unwrap();
+        assert_eq!(client.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn serve_n_returns_after_n_messages() {
+        let json_rpc = JsonRpc::new().add("echo", echo);
+        let (client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        for id in 1..=3 {
+            client
+                .send(&format!(
+                    r#"{{"jsonrpc":"2.0","method":"echo","params":{},"id":{}}}"#,
+                    id, id
+                ))
+                .await
+                .unwrap();
+        }
+
+        let (reader, writer) = tokio::io::split(server);
+        serve_n(LineIo::new(reader, writer), &json_rpc, 2)
+            .await
+            .unwrap();
+
+        for id in 1..=2 {
+            assert_eq!(
+                client.recv().await.unwrap(),
+                Some(format!(
+                    "{{\"jsonrpc\":\"2.0\",\"result\":{},\"id\":{}}}\n",
+                    id, id
+                ))
+            );
+        }
+        assert_eq!(client.recv().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn replaced_registry_serves_new_methods() {
+        let registry = Arc::new(Registry::new(JsonRpc::new().add("echo", echo)));
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn({
+            let registry = Arc::clone(&registry);
+            async move {
+                let (reader, writer) = tokio::io::split(server);
+                serve_reloadable(LineIo::new(reader, writer), &registry).await
+            }
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        let admin = r#"{"jsonrpc":"2.0","method":"admin.echo","params":1,"id":1}"#;
+        client.send(admin).await.unwrap();
+        let response: Value = serde_json::from_str(&client.recv().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32601);
+
+        registry.replace(JsonRpc::new().add("echo", echo).add("admin.echo", echo));
+        client.send(admin).await.unwrap();
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":1}\n")
+        );
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    /// Serve `json_rpc` on a new pipe and return the client end.
+    fn connect_shared(
+        json_rpc: &Arc<JsonRpc>,
+    ) -> LineIo<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>> {
+        let (client, server) = tokio::io::duplex(1024);
+        let json_rpc = Arc::clone(json_rpc);
+        tokio::spawn(async move {
+            let (reader, writer) = tokio::io::split(server);
+            serve_io(LineIo::new(reader, writer), &json_rpc).await
+        });
+        let (reader, writer) = tokio::io::split(client);
+        LineIo::new(reader, writer)
+    }
+
+    #[tokio::test]
+    async fn each_connection_must_initialize() {
+        let json_rpc = Arc::new(
+            JsonRpc::new()
+                .add("initialize", echo)
+                .add("echo", echo)
+                .require_initialize("initialize"),
+        );
+        let mut first = connect_shared(&json_rpc);
+        let mut second = connect_shared(&json_rpc);
+        let echo_request = r#"{"jsonrpc":"2.0","method":"echo","params":1,"id":2}"#;
+
+        first
+            .send(r#"{"jsonrpc":"2.0","method":"initialize","params":{},"id":1}"#)
+            .await
+            .unwrap();
+        first.recv().await.unwrap().unwrap();
+        first.send(echo_request).await.unwrap();
+        assert_eq!(
+            first.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":1,\"id\":2}\n")
+        );
+
+        second.send(echo_request).await.unwrap();
+        let response: Value = serde_json::from_str(&second.recv().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32002);
+    }
+
+    #[tokio::test]
+    async fn shutdown_signal_wakes_waiting_tasks() {
+        let shutdown = ShutdownSignal::new();
+        let waiting = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!shutdown.is_shutdown_requested());
+
+        shutdown.signal();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(shutdown.is_shutdown_requested());
+        shutdown.wait().await;
+    }
+
+    #[tokio::test]
+    async fn oversized_line_is_rejected_and_skipped() {
+        let (client, server) = tokio::io::duplex(64);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            let io = LineIo::new(reader, writer).with_max_line_length(100);
+            serve_io(io, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        let oversized = format!(
+            r#"{{"jsonrpc":"2.0","method":"echo","params":"{}","id":1}}"#,
+            "x".repeat(10_000)
+        );
+        client.send(&oversized).await.unwrap();
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"echo","params":"ok","id":2}"#)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some(
+                "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32600,\"message\":\"Invalid Request\"},\"id\":null}\n"
+            )
+        );
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":\"ok\",\"id\":2}\n")
+        );
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    async fn build(steps: u32, sink: NotificationSink) -> Result<&'static str, Error> {
+        for step in 1..=steps {
+            sink.send("progress", step)?;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok("done")
+    }
+
+    #[tokio::test]
+    async fn handler_notifications_arrive_before_response() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add_with_sink("build", build);
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"build","params":2,"id":1}"#)
+            .await
+            .unwrap();
+
+        for expected in [
+            "{\"jsonrpc\":\"2.0\",\"method\":\"progress\",\"params\":1}\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"progress\",\"params\":2}\n",
+            "{\"jsonrpc\":\"2.0\",\"result\":\"done\",\"id\":1}\n",
+        ] {
+            assert_eq!(client.recv().await.unwrap().as_deref(), Some(expected));
+        }
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    /// Frame `body` with a `Content-Length` header and extra `headers`.
+    fn frame(headers: &str, body: &str) -> String {
+        format!("Content-Length: {}\r\n{}\r\n{}", body.len(), headers, body)
+    }
+
+    #[tokio::test]
+    async fn content_length_framing_round_trips() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            serve_io(ContentLengthIo::new(reader, writer), &json_rpc).await
+        });
+
+        let first = r#"{"jsonrpc":"2.0","method":"echo","params":"héllo","id":1}"#;
+        let second = r#"{"jsonrpc":"2.0","method":"echo","params":[1,2],"id":2}"#;
+        let input = format!(
+            "{}{}",
+            frame(
+                "Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n",
+                first
+            ),
+            frame("", second)
+        );
+        client.write_all(input.as_bytes()).await.unwrap();
+
+        let expected = format!(
+            "{}{}",
+            frame("", r#"{"jsonrpc":"2.0","result":"héllo","id":1}"#),
+            frame("", r#"{"jsonrpc":"2.0","result":[1,2],"id":2}"#)
+        );
+        let mut output = vec![0; expected.len()];
+        client.read_exact(&mut output).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn content_length_framing_requires_length_header() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(server);
+        let mut io = ContentLengthIo::new(reader, writer);
+
+        client
+            .write_all(b"Content-Type: application/json\r\n\r\n{}")
+            .await
+            .unwrap();
+
+        let error = io.recv().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Protocol error: Missing Content-Length header"
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_is_rejected_and_skipped() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            let io = ContentLengthIo::new(reader, writer).with_max_message_size(100);
+            serve_io(io, &json_rpc).await
+        });
+
+        let oversized = format!(
+            r#"{{"jsonrpc":"2.0","method":"echo","params":"{}","id":1}}"#,
+            "x".repeat(500)
+        );
+        let input = format!(
+            "{}{}",
+            frame("", &oversized),
+            frame(
+                "",
+                r#"{"jsonrpc":"2.0","method":"echo","params":"ok","id":2}"#
+            )
+        );
+        client.write_all(input.as_bytes()).await.unwrap();
+
+        let expected = format!(
+            "{}{}",
+            frame(
+                "",
+                r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#
+            ),
+            frame("", r#"{"jsonrpc":"2.0","result":"ok","id":2}"#)
+        );
+        let mut output = vec![0; expected.len()];
+        client.read_exact(&mut output).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn huge_content_length_is_not_allocated() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(server);
+        let mut io = ContentLengthIo::new(reader, writer);
+
+        client
+            .write_all(b"Content-Length: 1000000000000\r\n\r\n{}")
+            .await
+            .unwrap();
+        drop(client);
+
+        let error = io.recv().await.unwrap_err();
+        assert!(matches!(error, Error::InvalidRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn oversized_headers_are_rejected_and_skipped() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new().add("echo", echo);
+            let (reader, writer) = tokio::io::split(server);
+            serve_io(ContentLengthIo::new(reader, writer), &json_rpc).await
+        });
+
+        let long_line = format!("X-Padding: {}\r\n", "x".repeat(MAX_HEADER_LINE_LENGTH));
+        let many_lines = "X-Padding: x\r\n".repeat(MAX_HEADER_LINES + 1);
+        let writer = tokio::spawn(async move {
+            let request = r#"{"jsonrpc":"2.0","method":"echo","params":"ok","id":1}"#;
+            let input = format!(
+                "{}{}{}",
+                frame(&long_line, request),
+                frame(&many_lines, request),
+                frame("", request)
+            );
+            client.write_all(input.as_bytes()).await.unwrap();
+            client
+        });
+        let mut client = writer.await.unwrap();
+
+        let rejected = frame(
+            "",
+            r#"{"jsonrpc":"2.0","error":{"code":-32600,"message":"Invalid Request"},"id":null}"#,
+        );
+        let expected = format!(
+            "{}{}{}",
+            rejected,
+            rejected,
+            frame("", r#"{"jsonrpc":"2.0","result":"ok","id":1}"#)
+        );
+        let mut output = vec![0; expected.len()];
+        client.read_exact(&mut output).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn endless_header_line_is_not_buffered() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(server);
+        let mut io = ContentLengthIo::new(reader, writer);
+
+        let writer = tokio::spawn(async move {
+            let chunk = vec![b'x'; 64 * 1024];
+            for _ in 0..64 {
+                client.write_all(&chunk).await.unwrap();
+            }
+            client.write_all(b"\r\n\r\n").await.unwrap();
+        });
+
+        let error = io.recv().await.unwrap_err();
+        assert!(matches!(error, Error::InvalidRequest(_)));
+        writer.await.unwrap();
+    }
+
+    /// A cancellable handler that finishes with "done" after `wait` unless it
+    /// is cancelled first.
+    fn cancellable(wait: Duration) -> JsonRpc {
+        JsonRpc::new()
+            .add_cancellable(
+                "wait",
+                move |_params: Value, token: CancellationToken| async move {
+                    match tokio::time::timeout(wait, token.cancelled()).await {
+                        Ok(()) => Err(Error::Cancelled),
+                        Err(_) => Ok("done"),
+                    }
+                },
+            )
+            .with_concurrent_requests(4)
+    }
+
+    #[tokio::test]
+    async fn cancel_request_reaches_running_request() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = cancellable(Duration::from_secs(30));
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"wait","id":1}"#)
+            .await
+            .unwrap();
+        client
+            .send(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":1}}"#)
+            .await
+            .unwrap();
+
+        let response: Value = serde_json::from_str(&client.recv().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], -32800);
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_request_does_not_reach_other_connections() {
+        let json_rpc = Arc::new(cancellable(Duration::from_millis(200)));
+        let mut clients = Vec::new();
+        let mut served = Vec::new();
+        for _ in 0..2 {
+            let (client, server) = tokio::io::duplex(1024);
+            let json_rpc = Arc::clone(&json_rpc);
+            served.push(tokio::spawn(async move {
+                let (reader, writer) = tokio::io::split(server);
+                serve_duplex(reader, writer, &json_rpc).await
+            }));
+            let (reader, writer) = tokio::io::split(client);
+            clients.push(LineIo::new(reader, writer));
+        }
+
+        let (first, second) = clients.split_at_mut(1);
+        let (first, second) = (&mut first[0], &mut second[0]);
+        first
+            .send(r#"{"jsonrpc":"2.0","method":"wait","id":1}"#)
+            .await
+            .unwrap();
+        second
+            .send(r#"{"jsonrpc":"2.0","method":"wait","id":1}"#)
+            .await
+            .unwrap();
+        second
+            .send(r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":1}}"#)
+            .await
+            .unwrap();
+
+        let cancelled: Value =
+            serde_json::from_str(&second.recv().await.unwrap().unwrap()).unwrap();
+        assert_eq!(cancelled["error"]["code"], -32800);
+        assert_eq!(
+            first.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":\"done\",\"id\":1}\n")
+        );
+
+        drop(clients);
+        for served in served {
+            served.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_recv_keeps_partial_line() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(server);
+        let mut io = LineIo::new(reader, writer);
+
+        client.write_all(br#"{"jsonrpc":"2.0","#).await.unwrap();
+        let pending = tokio::time::timeout(Duration::from_millis(20), io.recv()).await;
+        assert!(pending.is_err());
+
+        client.write_all(b"\"id\":1}\n").await.unwrap();
+        assert_eq!(
+            io.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"id\":1}\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn dropped_recv_keeps_partial_frame() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(server);
+        let mut io = ContentLengthIo::new(reader, writer);
+
+        let message = frame("", r#"{"jsonrpc":"2.0","id":1}"#).into_bytes();
+        for part in [&message[..10], &message[10..30]] {
+            client.write_all(part).await.unwrap();
+            let pending = tokio::time::timeout(Duration::from_millis(20), io.recv()).await;
+            assert!(pending.is_err());
+        }
+
+        client.write_all(&message[30..]).await.unwrap();
+        assert_eq!(
+            io.recv().await.unwrap().as_deref(),
+            Some(r#"{"jsonrpc":"2.0","id":1}"#)
+        );
+    }
+
+    async fn sleep(millis: u64) -> Result<u64, Error> {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok(millis)
+    }
+
+    #[tokio::test]
+    async fn streamed_batch_responses_arrive_as_they_complete() {
+        let (client, server) = tokio::io::duplex(1024);
+        let served = tokio::spawn(async move {
+            let json_rpc = JsonRpc::new()
+                .add("sleep", sleep)
+                .with_batch_echo("ref")
+                .stream_batch_responses(true);
+            let (reader, writer) = tokio::io::split(server);
+            serve_duplex(reader, writer, &json_rpc).await
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut client = LineIo::new(reader, writer);
+        let started = tokio::time::Instant::now();
+        client
+            .send(concat!(
+                r#"[{"jsonrpc":"2.0","method":"sleep","params":400,"id":1},"#,
+                r#"{"jsonrpc":"2.0","method":"sleep","params":10,"id":2,"ref":"b"},"#,
+                r#"{"jsonrpc":"2.0","method":"sleep","params":150,"id":3}]"#
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":10,\"id\":2,\"ref\":\"b\"}\n")
+        );
+        assert!(started.elapsed() < Duration::from_millis(150));
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":150,\"id\":3}\n")
+        );
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert_eq!(
+            client.recv().await.unwrap().as_deref(),
+            Some("{\"jsonrpc\":\"2.0\",\"result\":400,\"id\":1}\n")
+        );
+
+        drop(client);
+        served.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+//! Integration tests for the tower service adapter.
+//!
+//! Run test:
+//!
+//! ```shell
+//! cargo test --test tower_service --features tower
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use json_rpc::{Error, JsonRpc};
+    use serde_json::Value;
+    use tower_service::Service;
+
+    async fn echo(params: Value) -> Result<Value, Error> {
+        Ok(params)
+    }
+
+    #[tokio::test]
+    async fn service_answers_request_strings() {
+        let mut service = JsonRpc::new().add("echo", echo).into_tower_service();
+
+        std::future::poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .unwrap();
+        let response = service
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":[1,2],"id":1}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.as_deref(),
+            Some(r#"{"jsonrpc":"2.0","result":[1,2],"id":1}"#)
+        );
+
+        let mut clone = service.clone();
+        let response = clone
+            .call(r#"{"jsonrpc":"2.0","method":"echo","params":"hi"}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response, None);
+    }
+}